@@ -0,0 +1,245 @@
+use chrono::{DateTime, Duration, NaiveDateTime, Timelike, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_ARCHIVE_DIRECTORY: &str = "archive";
+
+/// Runs from the last day are all kept, older ones only once an hour.
+pub const DEFAULT_FULL_HISTORY_HOURS: i64 = 24;
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// The kinds of file kept in the archive, one of each per run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ArchiveKind {
+    Status,
+    Aggregated,
+}
+
+impl ArchiveKind {
+    fn prefix(&self) -> &'static str {
+        match self {
+            ArchiveKind::Status => "status",
+            ArchiveKind::Aggregated => "aggregated",
+        }
+    }
+}
+
+/// Files are named like `status-20240601T120000Z.json.gz`, so they sort by time.
+pub fn archive_path(directory: &Path, kind: &ArchiveKind, fetched_at: &DateTime<Utc>) -> PathBuf {
+    return directory.join(format!(
+        "{}-{}.json.gz",
+        kind.prefix(),
+        fetched_at.format(TIMESTAMP_FORMAT)
+    ));
+}
+
+/// The kind and time of an archived file from its name. Older archives were
+/// written uncompressed, so plain `.json` is accepted too.
+pub fn parse_archive_name(name: &str) -> Option<(ArchiveKind, DateTime<Utc>)> {
+    let stem = name
+        .strip_suffix(".json.gz")
+        .or_else(|| name.strip_suffix(".json"))?;
+    let (prefix, timestamp) = stem.split_once('-')?;
+    let kind = [ArchiveKind::Status, ArchiveKind::Aggregated]
+        .into_iter()
+        .find(|kind| kind.prefix() == prefix)?;
+    let fetched_at = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+    return Some((kind, fetched_at.and_utc()));
+}
+
+/// Save a gzipped copy of `value` to the archive.
+pub fn write_to_archive<T: Serialize>(
+    directory: &Path,
+    kind: &ArchiveKind,
+    fetched_at: &DateTime<Utc>,
+    value: &T,
+) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(directory)?;
+    let path = archive_path(directory, kind, fetched_at);
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&serde_json::to_vec(value)?)?;
+    std::fs::write(&path, encoder.finish()?)?;
+    return Ok(path);
+}
+
+/// Read a single archived file, gzipped or not.
+pub fn read_archived<T: DeserializeOwned>(path: &Path) -> std::io::Result<T> {
+    let contents = std::fs::read(path)?;
+    if path.extension().map(|extension| extension == "gz") == Some(true) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(contents.as_slice()).read_to_end(&mut decompressed)?;
+        return Ok(serde_json::from_slice(&decompressed)?);
+    }
+    return Ok(serde_json::from_slice(&contents)?);
+}
+
+/// Every archived file of the given kind, oldest first.
+pub fn archived_paths(
+    directory: &Path,
+    kind: &ArchiveKind,
+) -> std::io::Result<Vec<(DateTime<Utc>, PathBuf)>> {
+    let mut paths: Vec<(DateTime<Utc>, PathBuf)> = std::fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?;
+            let (path_kind, fetched_at) = parse_archive_name(name)?;
+            return (path_kind == *kind).then_some((fetched_at, path));
+        })
+        .collect();
+    paths.sort();
+    // a run archived both before and after compression is only read once
+    paths.dedup_by(|later, earlier| later.0 == earlier.0);
+    return Ok(paths);
+}
+
+/// Read every archived file of the given kind, oldest first.
+pub fn read_archive<T: DeserializeOwned>(
+    directory: &Path,
+    kind: &ArchiveKind,
+) -> std::io::Result<Vec<T>> {
+    let mut values = Vec::new();
+    for (_, path) in archived_paths(directory, kind)? {
+        values.push(read_archived(&path)?);
+    }
+    return Ok(values);
+}
+
+/// The archived files to delete so that only the first run of each hour is
+/// kept once it is more than `full_history_hours` old.
+pub fn files_to_prune(
+    names: &[String],
+    now: &DateTime<Utc>,
+    full_history_hours: i64,
+) -> Vec<String> {
+    let cutoff = *now - Duration::hours(full_history_hours);
+    let mut parsed: Vec<(ArchiveKind, DateTime<Utc>, &String)> = names
+        .iter()
+        .filter_map(|name| {
+            parse_archive_name(name).map(|(kind, fetched_at)| (kind, fetched_at, name))
+        })
+        .collect();
+    parsed.sort();
+
+    let mut kept_hours: BTreeSet<(ArchiveKind, DateTime<Utc>)> = BTreeSet::new();
+    let mut pruned = Vec::new();
+    for (kind, fetched_at, name) in parsed {
+        if fetched_at >= cutoff {
+            continue;
+        }
+        let hour = fetched_at
+            .with_minute(0)
+            .and_then(|hour| hour.with_second(0))
+            .and_then(|hour| hour.with_nanosecond(0))
+            .unwrap();
+        if !kept_hours.insert((kind, hour)) {
+            pruned.push(name.clone());
+        }
+    }
+    return pruned;
+}
+
+/// Roll up old runs in the archive to one an hour, returning how many files
+/// were deleted.
+pub fn prune_archive(
+    directory: &Path,
+    now: &DateTime<Utc>,
+    full_history_hours: i64,
+) -> std::io::Result<usize> {
+    let names: Vec<String> = std::fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str().map(|name| name.to_string()))
+        .collect();
+    let pruned = files_to_prune(&names, now, full_history_hours);
+    for name in &pruned {
+        std::fs::remove_file(directory.join(name))?;
+    }
+    return Ok(pruned.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn time(hour: u32, minute: u32) -> DateTime<Utc> {
+        return Utc.with_ymd_and_hms(2024, 6, 1, hour, minute, 0).unwrap();
+    }
+
+    #[test]
+    fn parses_archive_names() {
+        assert_eq!(
+            parse_archive_name("status-20240601T123000Z.json.gz"),
+            Some((ArchiveKind::Status, time(12, 30)))
+        );
+        assert_eq!(
+            parse_archive_name("aggregated-20240601T123000Z.json"),
+            Some((ArchiveKind::Aggregated, time(12, 30)))
+        );
+        let path = archive_path(Path::new("archive"), &ArchiveKind::Status, &time(9, 5));
+        assert_eq!(
+            parse_archive_name(path.file_name().unwrap().to_str().unwrap()),
+            Some((ArchiveKind::Status, time(9, 5)))
+        );
+        for name in [
+            "trajectories-20240601T123000Z.json",
+            "status-20240601T1230Z.json",
+            "status-20240601T123000Z.csv",
+            "status.json",
+        ] {
+            assert_eq!(parse_archive_name(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn prunes_to_one_an_hour_after_a_day() {
+        let names: Vec<String> = [
+            // more than a day before `now`
+            "status-20240601T100000Z.json.gz",
+            "status-20240601T101000Z.json.gz",
+            "aggregated-20240601T101000Z.json.gz",
+            "status-20240601T105000Z.json",
+            "status-20240601T110000Z.json.gz",
+            // within the last day
+            "status-20240602T100000Z.json.gz",
+            "status-20240602T101000Z.json.gz",
+            "notes.txt",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+        let now = Utc.with_ymd_and_hms(2024, 6, 2, 11, 0, 0).unwrap();
+        assert_eq!(
+            files_to_prune(&names, &now, DEFAULT_FULL_HISTORY_HOURS),
+            vec![
+                "status-20240601T101000Z.json.gz".to_string(),
+                "status-20240601T105000Z.json".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_back_what_was_written() {
+        let directory = std::env::temp_dir().join(format!("archive-test-{}", std::process::id()));
+        write_to_archive(&directory, &ArchiveKind::Status, &time(12, 0), &vec![1, 2]).unwrap();
+        std::fs::write(
+            archive_path(&directory, &ArchiveKind::Status, &time(11, 0)).with_extension(""),
+            "[0]",
+        )
+        .unwrap();
+        std::fs::write(
+            archive_path(&directory, &ArchiveKind::Status, &time(12, 0)).with_extension(""),
+            "[1, 2]",
+        )
+        .unwrap();
+        let values: Vec<Vec<i32>> = read_archive(&directory, &ArchiveKind::Status).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(values, vec![vec![0], vec![1, 2]]);
+    }
+}
@@ -1,54 +1,509 @@
-use rand::seq::SliceRandom;
-use std::collections::{HashMap, HashSet};
+use rand::Rng;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
 
+use election_2024::archive::{
+    prune_archive, write_to_archive, ArchiveKind, DEFAULT_ARCHIVE_DIRECTORY,
+    DEFAULT_FULL_HISTORY_HOURS,
+};
+use election_2024::constituencies::{areas_of, nation_of, region_of};
+use election_2024::coverage::{
+    coverage_report, identify_constituencies, impute_missing, remove_duplicates,
+};
+use election_2024::exact::exact_summaries;
+use election_2024::normalisation::{is_overround_flagged, normalise_probabilities, overround};
+use election_2024::notional::{notional_2019_winner, notional_comparison};
+use election_2024::parties::{party_info, PartyAliases};
+use election_2024::quality::{is_thin_market, market_quality, shrink_thin_markets};
+use election_2024::results::{apply_results, read_results, results_summary};
+use election_2024::sensitivity::{most_influential_seats, seat_sensitivities};
+use election_2024::simulation::{
+    parse_blocs, run_monte_carlo, Bloc, SimulationConfig, SimulationResults, MAJORITY_SEATS,
+    TOTAL_SEATS,
+};
 use election_2024::{
-    to_simple_summary, AggregatedStats, ConstituencyAggregated, ConstituencyStats,
-    ConstituencyStatus, MonteCarloSummary, Party, PartyName, Status, NUMBER_OF_SIMULATIONS,
+    get_option, to_simple_summary, AggregatedStats, ArchivedAggregate, ConstituencyAggregated,
+    ConstituencyStats, ConstituencyStatus, DeclaredResult, ImputationStrategy, MonteCarloSummary,
+    MonteCarloSummarySimple, NormalisationStrategy, Party, PartyName, RegionBreakdown,
+    SimulationMode, Status, ThinMarketAdjustment, ThinMarketReliance, DEFAULT_BLOCS,
+    DEFAULT_NATIONAL_SHOCK, DEFAULT_NATION_SHOCK, DEFAULT_NUMBER_OF_SIMULATIONS,
+    DEFAULT_OVERROUND_THRESHOLD, DEFAULT_PERCENTILES, DEFAULT_QUALITY_THRESHOLD,
+    DEFAULT_SENSITIVE_SEATS, DEFAULT_SENSITIVITY_SHIFT,
 };
 
+const DEFAULT_RESULTS_PATH: &str = "out/results.csv";
+
 fn main() {
     let input = std::fs::read("out/constituencies.json").unwrap();
-    let constituencies: Status = serde_json::from_slice(&input).unwrap();
+    let raw_constituencies: Status = serde_json::from_slice(&input).unwrap();
+
+    // check we have a market for every seat before doing anything else
+    let imputation = get_imputation_strategy();
+    let identified_constituencies = identify_constituencies(&raw_constituencies.constituencies);
+    let coverage = coverage_report(&identified_constituencies, &imputation);
+    let unique_constituencies = remove_duplicates(&identified_constituencies);
+
+    // the markets' probabilities don't always sum to 1, so normalise them
+    // before anything else looks at them
+    let normalisation = get_normalisation_strategy();
+    let overround_threshold = get_overround_threshold();
+    let mut overrounds: Vec<f64> = unique_constituencies
+        .iter()
+        .map(|constituency| overround(&constituency.parties))
+        .collect();
+    let mut normalised_constituencies: Vec<ConstituencyStatus> = unique_constituencies
+        .iter()
+        .map(|constituency| ConstituencyStatus {
+            parties: normalise_probabilities(&constituency.parties, &normalisation),
+            ..constituency.clone()
+        })
+        .collect();
+
+    // fill in the seats without a market, so that they still count
+    let market_count = normalised_constituencies.len();
+    let imputed_constituencies = impute_missing(&normalised_constituencies, &imputation);
+    overrounds.extend(imputed_constituencies.iter().map(|_| 1.0));
+    normalised_constituencies.extend(imputed_constituencies);
+
+    let constituencies = Status {
+        fetched_at: raw_constituencies.fetched_at,
+        market_errors: raw_constituencies.market_errors.clone(),
+        unparsed_answers: raw_constituencies.unparsed_answers.clone(),
+        constituencies: normalised_constituencies,
+    };
+
+    // imputed seats have no market, so count as the thinnest of all
+    let quality_threshold = get_quality_threshold();
+    let qualities: Vec<f64> = constituencies
+        .constituencies
+        .iter()
+        .enumerate()
+        .map(|(i, constituency)| {
+            if i >= market_count {
+                return 0.0;
+            }
+            return market_quality(&constituency.market, &constituencies.fetched_at);
+        })
+        .collect();
+    let thin_market_adjustment = get_thin_market_adjustment();
+    let shrunk_constituencies = shrink_thin_markets(
+        &constituencies.constituencies,
+        &qualities,
+        quality_threshold,
+        &thin_market_adjustment,
+    );
+
+    // on election night, declared seats are fixed to their winner and only
+    // the rest are simulated
+    let results = get_results();
+    let simulated_constituencies = apply_results(&shrunk_constituencies, &results);
+    let declared_winners: HashMap<&str, &PartyName> = results
+        .iter()
+        .map(|result| (result.ons_code.as_str(), &result.winner))
+        .collect();
 
     let constituencies_aggregated = constituencies
         .constituencies
         .iter()
-        .map(|constituency| {
+        .zip(&overrounds)
+        .zip(&qualities)
+        .enumerate()
+        .map(|(i, ((constituency, overround), quality))| {
             let stats = make_constituency_stats(&constituency.parties);
             return ConstituencyAggregated {
+                ons_code: constituency.ons_code.clone(),
                 constituency: constituency.constituency.clone(),
                 parties: constituency.parties.clone(),
                 manifold_url: constituency.manifold_url.clone(),
+                nation: nation_of(constituency),
+                region: region_of(constituency),
+                notional_2019_winner: notional_2019_winner(&constituency.ons_code).cloned(),
+                overround: *overround,
+                overround_flagged: is_overround_flagged(*overround, overround_threshold),
+                imputed: i >= market_count,
+                market: constituency.market.clone(),
+                market_quality: *quality,
+                thin_market: is_thin_market(*quality, quality_threshold),
+                declared_winner: declared_winners
+                    .get(constituency.ons_code.as_str())
+                    .map(|winner| (*winner).clone()),
                 stats,
             };
         })
         .collect();
 
-    let stats = get_stats(&constituencies.constituencies);
+    // the favourites, with declared seats counted for their winner
+    let favourite_constituencies = apply_results(&constituencies.constituencies, &results);
+    let stats = get_stats(&favourite_constituencies);
 
-    let monte_carlo_results = run_monte_carlo(&constituencies.constituencies);
-    let monte_carlo_summaries = get_montecarlo_summary(&monte_carlo_results);
-    let monte_carlo_summaries_simple = monte_carlo_summaries
+    let config = SimulationConfig {
+        mode: get_simulation_mode(),
+        number_of_simulations: get_number_of_simulations(),
+        seed: get_seed(),
+        blocs: get_blocs(),
+    };
+    let percentiles = get_percentiles();
+    let monte_carlo_results = run_monte_carlo(&simulated_constituencies, &config);
+    let scenarios = monte_carlo_results.scenarios();
+    for bloc in scenarios
+        .blocs
         .iter()
-        .map(to_simple_summary)
-        .collect();
+        .filter(|bloc| !bloc.not_standing.is_empty())
+    {
+        eprintln!(
+            "Bloc `{}` has members who aren't standing anywhere: {}",
+            bloc.name,
+            bloc.not_standing
+                .iter()
+                .map(|party| party.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+    }
+    let monte_carlo_summaries_simple = match config.mode {
+        SimulationMode::Exact => exact_summaries(
+            &simulated_constituencies,
+            MAJORITY_SEATS,
+            &percentiles,
+            config.number_of_simulations,
+        ),
+        _ => to_simple_summaries(&monte_carlo_results.summaries(), &percentiles),
+    };
+    // keep the independent model around as a baseline to compare against
+    let baseline_monte_carlo_summary = match config.mode {
+        SimulationMode::Independent => None,
+        // the simulations were already independent
+        SimulationMode::Exact => Some(to_simple_summaries(
+            &monte_carlo_results.summaries(),
+            &percentiles,
+        )),
+        _ => {
+            let baseline_results = run_monte_carlo(
+                &simulated_constituencies,
+                &SimulationConfig {
+                    mode: SimulationMode::Independent,
+                    ..config.clone()
+                },
+            );
+            Some(to_simple_summaries(
+                &baseline_results.summaries(),
+                &percentiles,
+            ))
+        }
+    };
+    let sensitivity_shift = get_sensitivity_shift();
+    let sensitivity = most_influential_seats(
+        &seat_sensitivities(&simulated_constituencies, sensitivity_shift),
+        get_sensitive_seats(),
+    );
+    let regions = get_regions(
+        &favourite_constituencies,
+        &simulated_constituencies,
+        &monte_carlo_results,
+        &config,
+        &percentiles,
+    );
 
     let mut sorted_stats: Vec<(PartyName, i32)> = stats.into_iter().collect();
     sorted_stats.sort_by(|a, b| (b.1, &b.0).cmp(&(a.1, &a.0)));
 
+    // the page needs the colours etc. of every party which appears
+    let party_names: BTreeSet<PartyName> = constituencies
+        .constituencies
+        .iter()
+        .flat_map(|constituency| constituency.parties.iter().map(|party| party.name.clone()))
+        .collect();
+
     let aggregates = AggregatedStats {
         fetched_at: constituencies.fetched_at,
+        parties: party_names.iter().map(party_info).collect(),
         constituencies: constituencies_aggregated,
         winning_constituencies: sorted_stats,
+        seed: config.seed,
+        number_of_simulations: config.number_of_simulations,
+        simulation_mode: config.mode,
         monte_carlo_summary: monte_carlo_summaries_simple,
+        baseline_monte_carlo_summary,
+        scenarios,
+        normalisation,
+        overround_threshold,
+        coverage,
+        quality_threshold,
+        thin_market_adjustment,
+        thin_market_reliance: get_thin_market_reliance(
+            &constituencies.constituencies,
+            &qualities,
+            quality_threshold,
+        ),
+        results: results_summary(&results, TOTAL_SEATS),
+        regions,
+        notional_2019: notional_comparison(&favourite_constituencies, &monte_carlo_results),
+        pivotal_seats: monte_carlo_results.pivotal_seats(&simulated_constituencies),
+        sensitivity_shift,
+        sensitivity,
     };
 
     // output the aggregate stats
     let output = serde_json::to_string(&aggregates).unwrap();
     std::fs::write("out/election-2024.json", output).unwrap();
+
+    // and keep what is read back in the archive, so the history isn't lost
+    let archive_directory =
+        get_option("archive-dir").unwrap_or(DEFAULT_ARCHIVE_DIRECTORY.to_string());
+    let archive_directory = Path::new(&archive_directory);
+    write_to_archive(
+        archive_directory,
+        &ArchiveKind::Aggregated,
+        &aggregates.fetched_at,
+        &ArchivedAggregate::from_aggregated(&aggregates),
+    )
+    .unwrap();
+    // this is the last step to write to the archive, so tidy it up
+    let pruned = prune_archive(
+        archive_directory,
+        &aggregates.fetched_at,
+        DEFAULT_FULL_HISTORY_HOURS,
+    )
+    .unwrap();
+    if pruned > 0 {
+        println!("Pruned {} old runs from the archive", pruned);
+    }
+}
+
+fn get_seed() -> u64 {
+    // pick a fresh seed if none is given, it is recorded in the output so the
+    // run can be reproduced
+    return get_option("seed")
+        .map(|value| value.parse::<u64>().expect("seed must be a u64"))
+        .unwrap_or_else(|| rand::thread_rng().gen());
+}
+
+fn get_number_of_simulations() -> usize {
+    return get_option("simulations")
+        .map(|value| {
+            value
+                .parse::<usize>()
+                .expect("simulations must be a number")
+        })
+        .unwrap_or(DEFAULT_NUMBER_OF_SIMULATIONS);
+}
+
+fn get_percentiles() -> Vec<f64> {
+    // a comma separated list, e.g. `1,10,25,75,90,99`
+    return get_option("percentiles")
+        .map(|value| {
+            value
+                .split(',')
+                .map(|percentile| {
+                    percentile
+                        .trim()
+                        .parse::<f64>()
+                        .expect("percentiles must be numbers")
+                })
+                .collect()
+        })
+        .unwrap_or(DEFAULT_PERCENTILES.to_vec());
+}
+
+fn get_sensitivity_shift() -> f64 {
+    return get_option("sensitivity-shift")
+        .map(|value| {
+            value
+                .parse::<f64>()
+                .expect("sensitivity-shift must be a number")
+        })
+        .unwrap_or(DEFAULT_SENSITIVITY_SHIFT);
+}
+
+fn get_sensitive_seats() -> usize {
+    return get_option("sensitive-seats")
+        .map(|value| {
+            value
+                .parse::<usize>()
+                .expect("sensitive-seats must be a number")
+        })
+        .unwrap_or(DEFAULT_SENSITIVE_SEATS);
+}
+
+fn get_blocs() -> Vec<Bloc> {
+    let blocs = get_option("blocs").unwrap_or(DEFAULT_BLOCS.to_string());
+    return parse_blocs(&blocs).unwrap();
 }
 
-fn make_constituency_stats(parties: &Vec<Party>) -> ConstituencyStats {
+fn get_normalisation_strategy() -> NormalisationStrategy {
+    let strategy = get_option("normalisation").unwrap_or("proportional".to_string());
+    return match strategy.as_str() {
+        "none" => NormalisationStrategy::None,
+        "proportional" => NormalisationStrategy::Proportional,
+        "power" => NormalisationStrategy::Power,
+        _ => panic!("Unknown normalisation strategy: {}", strategy),
+    };
+}
+
+/// The declared results so far, from `--results` or `out/results.csv` if it
+/// exists.
+fn get_results() -> Vec<DeclaredResult> {
+    let path = match get_option("results") {
+        Some(path) => path,
+        None if Path::new(DEFAULT_RESULTS_PATH).exists() => DEFAULT_RESULTS_PATH.to_string(),
+        None => return Vec::new(),
+    };
+    let party_aliases = PartyAliases::load(get_option("party-aliases").as_deref());
+    return read_results(&path, &party_aliases).unwrap_or_else(|error| panic!("{}", error));
+}
+
+fn get_quality_threshold() -> f64 {
+    return get_option("quality-threshold")
+        .map(|value| {
+            value
+                .parse::<f64>()
+                .expect("quality-threshold must be a number")
+        })
+        .unwrap_or(DEFAULT_QUALITY_THRESHOLD);
+}
+
+fn get_thin_market_adjustment() -> ThinMarketAdjustment {
+    let adjustment = get_option("thin-markets").unwrap_or("none".to_string());
+    return match adjustment.as_str() {
+        "none" => ThinMarketAdjustment::None,
+        "shrink-region" => ThinMarketAdjustment::ShrinkToRegion,
+        "shrink-nation" => ThinMarketAdjustment::ShrinkToNation,
+        _ => panic!("Unknown thin market adjustment: {}", adjustment),
+    };
+}
+
+fn get_thin_market_reliance(
+    constituencies: &[ConstituencyStatus],
+    qualities: &[f64],
+    quality_threshold: f64,
+) -> Vec<ThinMarketReliance> {
+    let mut totals: BTreeMap<PartyName, (f64, f64)> = BTreeMap::new();
+    for (constituency, quality) in constituencies.iter().zip(qualities) {
+        let is_thin = is_thin_market(*quality, quality_threshold);
+        for party in &constituency.parties {
+            let (expected_seats, thin_expected_seats) =
+                totals.entry(party.name.clone()).or_insert((0.0, 0.0));
+            *expected_seats += party.probability;
+            if is_thin {
+                *thin_expected_seats += party.probability;
+            }
+        }
+    }
+    let mut reliance: Vec<ThinMarketReliance> = totals
+        .into_iter()
+        .map(
+            |(party, (expected_seats, thin_expected_seats))| ThinMarketReliance {
+                party,
+                expected_seats,
+                thin_expected_seats,
+            },
+        )
+        .collect();
+    // the parties leaning hardest on thin markets first
+    reliance.sort_by(|a, b| {
+        let share = |r: &ThinMarketReliance| r.thin_expected_seats / r.expected_seats.max(1e-9);
+        share(b).partial_cmp(&share(a)).unwrap()
+    });
+    return reliance;
+}
+
+fn get_imputation_strategy() -> ImputationStrategy {
+    let strategy = get_option("imputation").unwrap_or("regional".to_string());
+    return match strategy.as_str() {
+        "none" => ImputationStrategy::None,
+        "uniform" => ImputationStrategy::Uniform,
+        "regional" => ImputationStrategy::RegionalAverage,
+        _ => panic!("Unknown imputation strategy: {}", strategy),
+    };
+}
+
+fn get_overround_threshold() -> f64 {
+    return get_option("overround-threshold")
+        .map(|value| {
+            value
+                .parse::<f64>()
+                .expect("overround threshold must be a number")
+        })
+        .unwrap_or(DEFAULT_OVERROUND_THRESHOLD);
+}
+
+fn get_simulation_mode() -> SimulationMode {
+    let mode = get_option("simulation-mode").unwrap_or("independent".to_string());
+    let get_shock = |name: &str, default: f64| {
+        get_option(name)
+            .map(|value| value.parse::<f64>().expect("shock must be a number"))
+            .unwrap_or(default)
+    };
+    return match mode.as_str() {
+        "independent" => SimulationMode::Independent,
+        "exact" => SimulationMode::Exact,
+        "correlated" => SimulationMode::Correlated {
+            national_shock: get_shock("national-shock", DEFAULT_NATIONAL_SHOCK),
+            nation_shock: get_shock("nation-shock", DEFAULT_NATION_SHOCK),
+        },
+        _ => panic!("Unknown simulation mode: {}", mode),
+    };
+}
+
+fn to_simple_summaries(
+    summaries: &[MonteCarloSummary],
+    percentiles: &[f64],
+) -> Vec<MonteCarloSummarySimple> {
+    return summaries
+        .iter()
+        .map(|summary| to_simple_summary(summary, percentiles))
+        .collect();
+}
+
+/// The favourites and simulated seats in each area the simulation tracked.
+fn get_regions(
+    constituencies: &[ConstituencyStatus],
+    simulated_constituencies: &[ConstituencyStatus],
+    monte_carlo_results: &SimulationResults,
+    config: &SimulationConfig,
+    percentiles: &[f64],
+) -> Vec<RegionBreakdown> {
+    return monte_carlo_results
+        .areas
+        .iter()
+        .zip(&monte_carlo_results.area_seats)
+        .zip(monte_carlo_results.area_summaries())
+        .map(|((region, seats), summaries)| {
+            let in_region: Vec<ConstituencyStatus> = constituencies
+                .iter()
+                .filter(|constituency| areas_of(constituency).contains(region))
+                .cloned()
+                .collect();
+            let mut winning_constituencies: Vec<(PartyName, i32)> =
+                get_stats(&in_region).into_iter().collect();
+            winning_constituencies.sort_by(|a, b| (b.1, &b.0).cmp(&(a.1, &a.0)));
+            let monte_carlo_summary = match config.mode {
+                SimulationMode::Exact => {
+                    let simulated_in_region: Vec<ConstituencyStatus> = simulated_constituencies
+                        .iter()
+                        .filter(|constituency| areas_of(constituency).contains(region))
+                        .cloned()
+                        .collect();
+                    exact_summaries(
+                        &simulated_in_region,
+                        seats / 2 + 1,
+                        percentiles,
+                        config.number_of_simulations,
+                    )
+                }
+                _ => to_simple_summaries(&summaries, percentiles),
+            };
+            return RegionBreakdown {
+                region: region.clone(),
+                seats: *seats,
+                winning_constituencies,
+                monte_carlo_summary,
+            };
+        })
+        .collect();
+}
+
+fn make_constituency_stats(parties: &[Party]) -> ConstituencyStats {
     let find_probability = |party_name: PartyName| {
         parties
             .iter()
@@ -61,12 +516,12 @@ fn make_constituency_stats(parties: &Vec<Party>) -> ConstituencyStats {
     let green_probability = find_probability(PartyName::Green);
     let reform_probability = find_probability(PartyName::Reform);
     let other_probability = find_probability(PartyName::Other);
-    let favourite_percentage = parties.iter().nth(0).map(|party| party.probability);
-    let second_favourite_percentage = parties.iter().nth(1).map(|party| party.probability);
+    let favourite_percentage = parties.first().map(|party| party.probability);
+    let second_favourite_percentage = parties.get(1).map(|party| party.probability);
     let favourite_lead = favourite_percentage
         .zip(second_favourite_percentage)
         .map(|(favourite, second_favourite)| favourite - second_favourite);
-    let third_place_probability = parties.iter().nth(2).map(|party| party.probability);
+    let third_place_probability = parties.get(2).map(|party| party.probability);
 
     return ConstituencyStats {
         labour_probability,
@@ -80,31 +535,7 @@ fn make_constituency_stats(parties: &Vec<Party>) -> ConstituencyStats {
     };
 }
 
-fn median(xs: &[i32]) -> i32 {
-    return xs[xs.len() / 2];
-}
-
-fn mode(xs: &[i32]) -> i32 {
-    let mut counts: HashMap<i32, i32> = HashMap::new();
-    for x in xs {
-        if counts.contains_key(x) {
-            counts.insert(*x, counts.get(x).unwrap() + 1);
-        } else {
-            counts.insert(*x, 1);
-        }
-    }
-    let mut max_count = 0;
-    let mut mode = 0;
-    for (x, count) in counts {
-        if count > max_count {
-            max_count = count;
-            mode = x;
-        }
-    }
-    return mode;
-}
-
-fn get_stats(constituencies: &Vec<ConstituencyStatus>) -> HashMap<PartyName, i32> {
+fn get_stats(constituencies: &[ConstituencyStatus]) -> HashMap<PartyName, i32> {
     // figure out the most likely party in each constituency
 
     let mut party_counts: HashMap<PartyName, i32> = HashMap::new();
@@ -126,75 +557,3 @@ fn get_stats(constituencies: &Vec<ConstituencyStatus>) -> HashMap<PartyName, i32
     }
     return party_counts;
 }
-
-fn get_montecarlo_summary(
-    simulation_results: &Vec<HashMap<PartyName, i32>>,
-) -> Vec<MonteCarloSummary> {
-    let parties: HashSet<PartyName> = simulation_results
-        .iter()
-        .flat_map(|party_counts| party_counts.keys())
-        .cloned()
-        .collect();
-    let mut summaries: Vec<MonteCarloSummary> = Vec::new();
-    for party in parties {
-        let mut seats: Vec<i32> = simulation_results
-            .iter()
-            .map(|party_counts| *party_counts.get(&party).unwrap_or(&0))
-            .collect();
-        seats.sort();
-        let mode = mode(&seats);
-        let lower_5th = seats[(0.05 * seats.len() as f64) as usize];
-        let upper_95th = seats[(0.95 * seats.len() as f64) as usize];
-        let median = median(&seats);
-        let majority_percentage =
-            seats.iter().filter(|&x| *x > 325).count() as f64 / seats.len() as f64;
-        let summary = MonteCarloSummary {
-            party: party.clone(),
-            seats,
-            mode,
-            median,
-            lower_5th,
-            upper_95th,
-            majority_percentage,
-        };
-        summaries.push(summary);
-    }
-
-    // sort by the mode
-    summaries.sort_by(|a, b| b.median.cmp(&a.median));
-
-    return summaries;
-}
-
-fn run_monte_carlo(constituencies: &Vec<ConstituencyStatus>) -> Vec<HashMap<PartyName, i32>> {
-    // run a monte carlo simulation
-    // for each constituency, pick a party based on the probabilities
-    // and increment the count for that party
-    // do this a few thousand times
-    // and then output the results
-    let mut rng = rand::thread_rng();
-
-    let mut simulation_results: Vec<HashMap<PartyName, i32>> = Vec::new();
-    for _ in 0..NUMBER_OF_SIMULATIONS {
-        let mut party_counts: HashMap<PartyName, i32> = HashMap::new();
-        for constituency in constituencies {
-            // randomly pick a party based on the probabilities
-            let winner = constituency
-                .parties
-                .choose_weighted(&mut rng, |party| party.probability)
-                .unwrap();
-
-            if party_counts.contains_key(&winner.name) {
-                party_counts.insert(
-                    winner.name.clone(),
-                    party_counts.get(&winner.name).unwrap() + 1,
-                );
-            } else {
-                party_counts.insert(winner.name.clone(), 1);
-            }
-        }
-        simulation_results.push(party_counts);
-    }
-
-    return simulation_results;
-}
@@ -0,0 +1,19 @@
+use std::path::Path;
+
+use election_2024::archive::{read_archive, ArchiveKind, DEFAULT_ARCHIVE_DIRECTORY};
+use election_2024::time_series::make_time_series;
+use election_2024::{get_option, ArchivedAggregate, Status};
+
+fn main() {
+    let archive_directory =
+        get_option("archive-dir").unwrap_or(DEFAULT_ARCHIVE_DIRECTORY.to_string());
+    let archive_directory = Path::new(&archive_directory);
+    let statuses: Vec<Status> = read_archive(archive_directory, &ArchiveKind::Status).unwrap();
+    let aggregates: Vec<ArchivedAggregate> =
+        read_archive(archive_directory, &ArchiveKind::Aggregated).unwrap();
+
+    let time_series = make_time_series(&statuses, &aggregates);
+
+    let output = serde_json::to_string(&time_series).unwrap();
+    std::fs::write("out/time-series.json", output).unwrap();
+}
@@ -0,0 +1,312 @@
+#![recursion_limit = "512"]
+
+use std::path::Path;
+
+use election_2024::archive::{archive_path, read_archived, ArchiveKind, DEFAULT_ARCHIVE_DIRECTORY};
+use election_2024::calibration::{calibration_report, seat_interval_checks};
+use election_2024::parties::PartyAliases;
+use election_2024::results::read_results;
+use election_2024::{
+    get_option, inline_svg, ArchivedAggregate, CalibrationReport, PartyName, ReliabilityBin,
+    SeatIntervalCheck, Status,
+};
+
+const DEFAULT_NUMBER_OF_BINS: usize = 10;
+
+fn main() {
+    let party_aliases = PartyAliases::load(get_option("party-aliases").as_deref());
+    let results_path = get_option("results").expect("--results is required");
+    let results =
+        read_results(&results_path, &party_aliases).unwrap_or_else(|error| panic!("{}", error));
+
+    // any archived status can be scored, not just the latest
+    let status_option = get_option("status");
+    let status_path = status_option
+        .clone()
+        .unwrap_or("out/constituencies.json".to_string());
+    let status: Status = read_archived(Path::new(&status_path)).unwrap();
+    let number_of_bins = get_option("bins")
+        .map(|value| value.parse::<usize>().expect("bins must be a number"))
+        .unwrap_or(DEFAULT_NUMBER_OF_BINS);
+
+    let mut report = calibration_report(&status, &results, number_of_bins);
+
+    // the seat intervals have to come from the aggregate of the same
+    // download, which for an archived status is the archived aggregate
+    let aggregated_path = get_option("aggregated").unwrap_or_else(|| match status_option {
+        Some(_) => {
+            let archive_directory =
+                get_option("archive-dir").unwrap_or(DEFAULT_ARCHIVE_DIRECTORY.to_string());
+            archive_path(
+                Path::new(&archive_directory),
+                &ArchiveKind::Aggregated,
+                &status.fetched_at,
+            )
+            .to_string_lossy()
+            .to_string()
+        }
+        None => "out/election-2024.json".to_string(),
+    });
+    if Path::new(&aggregated_path).exists() {
+        let aggregate: ArchivedAggregate = read_archived(Path::new(&aggregated_path)).unwrap();
+        if aggregate.fetched_at != status.fetched_at {
+            panic!(
+                "{} was fetched at {}, but {} was fetched at {}",
+                aggregated_path, aggregate.fetched_at, status_path, status.fetched_at
+            );
+        }
+        let intervals: Vec<(PartyName, i32, i32, i32)> = aggregate
+            .monte_carlo_summary
+            .into_iter()
+            .map(|summary| {
+                (
+                    summary.party,
+                    summary.median,
+                    summary.lower_5th,
+                    summary.upper_95th,
+                )
+            })
+            .collect();
+        report.seat_intervals = seat_interval_checks(&intervals, &results);
+    }
+
+    let output = serde_json::to_string(&report).unwrap();
+    std::fs::write("out/calibration.json", output).unwrap();
+    std::fs::write("out/accuracy.html", render_accuracy_page(&report)).unwrap();
+}
+
+fn render_accuracy_page(report: &CalibrationReport) -> String {
+    let tree = html::root::Html::builder()
+        .lang("en")
+        .head(|head| {
+            head.meta(|meta| meta.charset("utf-8"))
+                .meta(|meta| {
+                    meta.name("viewport")
+                        .content("width=device-width, initial-scale=1")
+                })
+                .title(|title| title.text("Manifold UK General Election 2024: accuracy"))
+        })
+        .body(|body| {
+            body.style("margin: 0 auto; padding: 0 20px; max-width: 820px;");
+
+            let mut heading = html::content::Heading1::builder();
+            heading.text("How accurate were the markets?");
+            body.push(heading.build());
+
+            let mut scores_paragraph = html::text_content::Paragraph::builder();
+            scores_paragraph.text(format!(
+                "Scoring the markets fetched at {} UTC against the results of {} seats. Brier score: {:.4} (0 is perfect). Log loss: {:.4}.",
+                report.fetched_at.format("%Y-%m-%d %H:%M:%S"),
+                report.seats_scored,
+                report.brier_score,
+                report.log_loss
+            ));
+            body.push(scores_paragraph.build());
+
+            let mut calibration_heading = html::content::Heading2::builder();
+            calibration_heading.text("Calibration");
+            body.push(calibration_heading.build());
+            let mut calibration_paragraph = html::text_content::Paragraph::builder();
+            calibration_paragraph.text("Every party's probability in every seat, grouped into bins, against how often those parties actually won. A perfectly calibrated market sits on the diagonal.");
+            body.push(calibration_paragraph.build());
+            body.push(make_reliability_chart(&report.reliability));
+            body.push(make_reliability_table(&report.reliability));
+
+            if !report.seat_intervals.is_empty() {
+                let mut intervals_heading = html::content::Heading2::builder();
+                intervals_heading.text("Seat totals");
+                body.push(intervals_heading.build());
+                body.push(make_intervals_table(&report.seat_intervals));
+            }
+
+            return body;
+        })
+        .build();
+
+    return tree.to_string();
+}
+
+fn make_reliability_chart(bins: &[ReliabilityBin]) -> html::text_content::Division {
+    let size = 300.0;
+    let margin = 30.0;
+    let plot = size - 2.0 * margin;
+    let x = |probability: f64| margin + probability * plot;
+    let y = |probability: f64| size - margin - probability * plot;
+
+    let mut svg = format!(
+        "<svg xmlns='http://www.w3.org/2000/svg' width='{}' height='{}' font-size='10'>",
+        size, size
+    );
+    svg.push_str(&format!(
+        "<rect x='{}' y='{}' width='{}' height='{}' fill='none' stroke='#999'/>",
+        margin, margin, plot, plot
+    ));
+    svg.push_str(&format!(
+        "<line x1='{}' y1='{}' x2='{}' y2='{}' stroke='#999' stroke-dasharray='4'/>",
+        x(0.0),
+        y(0.0),
+        x(1.0),
+        y(1.0)
+    ));
+    for tick in [0.0, 0.5, 1.0] {
+        svg.push_str(&format!(
+            "<text x='{}' y='{}' text-anchor='middle'>{}</text>",
+            x(tick),
+            size - margin + 14.0,
+            tick
+        ));
+        svg.push_str(&format!(
+            "<text x='{}' y='{}' text-anchor='end'>{}</text>",
+            margin - 4.0,
+            y(tick) + 3.0,
+            tick
+        ));
+    }
+    svg.push_str(&format!(
+        "<text x='{}' y='{}' text-anchor='middle'>Predicted</text>",
+        size / 2.0,
+        size - 4.0
+    ));
+    svg.push_str(&format!(
+        "<text x='10' y='{}' text-anchor='middle' transform='rotate(-90 10 {})'>Observed</text>",
+        size / 2.0,
+        size / 2.0
+    ));
+
+    let filled_bins: Vec<&ReliabilityBin> = bins.iter().filter(|bin| bin.count > 0).collect();
+    let points: Vec<String> = filled_bins
+        .iter()
+        .map(|bin| {
+            format!(
+                "{:.1},{:.1}",
+                x(bin.mean_predicted),
+                y(bin.observed_frequency)
+            )
+        })
+        .collect();
+    svg.push_str(&format!(
+        "<polyline fill='none' stroke='#E4003B' stroke-width='1.5' points='{}'/>",
+        points.join(" ")
+    ));
+    // bigger dots for bins with more predictions in them
+    let max_count = filled_bins.iter().map(|bin| bin.count).max().unwrap_or(1);
+    for bin in &filled_bins {
+        svg.push_str(&format!(
+            "<circle cx='{:.1}' cy='{:.1}' r='{:.1}' fill='#E4003B'/>",
+            x(bin.mean_predicted),
+            y(bin.observed_frequency),
+            2.0 + 4.0 * (bin.count as f64 / max_count as f64).sqrt()
+        ));
+    }
+    svg.push_str("</svg>");
+
+    return inline_svg(
+        &svg,
+        "Reliability curve: predicted probability against observed frequency",
+    );
+}
+
+fn make_reliability_table(bins: &[ReliabilityBin]) -> html::tables::Table {
+    let mut table = html::tables::Table::builder();
+    table.table_row(|row| {
+        row.table_header(|header| {
+            header.text("Predicted");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("Count");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("Mean predicted");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("Observed");
+            return header;
+        });
+        return row;
+    });
+    for bin in bins {
+        let row = html::tables::TableRow::builder()
+            .table_cell(|data| {
+                data.text(format!(
+                    "{:.0}% - {:.0}%",
+                    bin.lower * 100.0,
+                    bin.upper * 100.0
+                ));
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(bin.count.to_string());
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(format!("{:.1}%", bin.mean_predicted * 100.0));
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(format!("{:.1}%", bin.observed_frequency * 100.0));
+                return data;
+            })
+            .build();
+        table.push(row);
+    }
+    return table.build();
+}
+
+fn make_intervals_table(intervals: &[SeatIntervalCheck]) -> html::tables::Table {
+    let mut table = html::tables::Table::builder();
+    table.table_row(|row| {
+        row.table_header(|header| {
+            header.text("Party");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("Actual seats");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("Simulated median");
+            header.push(html::inline_text::LineBreak::builder().build());
+            header.text("[5th - 95th percentile]");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("Within interval");
+            return header;
+        });
+        return row;
+    });
+    for interval in intervals {
+        let row = html::tables::TableRow::builder()
+            .table_cell(|data| {
+                data.text(interval.party.to_string());
+                data.text(" ");
+                data.text(interval.party.to_emoji());
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(interval.actual_seats.to_string());
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(format!(
+                    "{} [{} - {}]",
+                    interval.median, interval.lower_5th, interval.upper_95th
+                ));
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(if interval.within_interval {
+                    "✅"
+                } else {
+                    "❌"
+                });
+                return data;
+            })
+            .build();
+        table.push(row);
+    }
+    return table.build();
+}
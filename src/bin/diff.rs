@@ -0,0 +1,94 @@
+use std::path::Path;
+
+use election_2024::archive::{
+    archived_paths, read_archived, ArchiveKind, DEFAULT_ARCHIVE_DIRECTORY,
+};
+use election_2024::diff::{change_report, Snapshot};
+use election_2024::{get_option, AggregatedStats, ArchivedAggregate, Status};
+
+/// How many of the largest probability changes to report by default.
+const DEFAULT_NUMBER_OF_CHANGES: usize = 20;
+
+fn main() {
+    let (before, after) = match get_option("before") {
+        Some(before) => (
+            read_snapshot(&before),
+            read_snapshot(&get_option("after").unwrap_or("out/election-2024.json".to_string())),
+        ),
+        // otherwise compare the latest run against the one before it in the archive
+        None => {
+            let after = latest_run();
+            match previous_archived_run(&after) {
+                Some(before) => (before, after),
+                None => {
+                    println!("No earlier run in the archive to compare against");
+                    return;
+                }
+            }
+        }
+    };
+    let number_of_changes = get_option("changes")
+        .map(|value| value.parse::<usize>().expect("changes must be a number"))
+        .unwrap_or(DEFAULT_NUMBER_OF_CHANGES);
+
+    let report = change_report(&before, &after, number_of_changes);
+
+    // render_html picks this up and adds a "What changed" section
+    let output = serde_json::to_string(&report).unwrap();
+    std::fs::write("out/changes.json", output).unwrap();
+
+    println!(
+        "{} seats flipped, {} markets added, {} removed",
+        report.flipped_seats.len(),
+        report.added_markets.len(),
+        report.removed_markets.len()
+    );
+}
+
+/// Read either the output of download_data or aggregate_data, telling them
+/// apart by whether there are simulation results.
+fn read_snapshot(path: &str) -> Snapshot {
+    let input =
+        std::fs::read(path).unwrap_or_else(|error| panic!("could not read {}: {}", path, error));
+    let value: serde_json::Value = serde_json::from_slice(&input).unwrap();
+    if value.get("monte_carlo_summary").is_some() {
+        let aggregated: AggregatedStats = serde_json::from_value(value).unwrap();
+        return Snapshot::from_aggregated(&aggregated);
+    }
+    let status: Status = serde_json::from_value(value).unwrap();
+    return Snapshot::from_status(&status);
+}
+
+/// The output of the latest download_data and aggregate_data, compared the
+/// same way as an archived run.
+fn latest_run() -> Snapshot {
+    let status: Status = read_archived(Path::new("out/constituencies.json")).unwrap();
+    let aggregate: ArchivedAggregate = read_archived(Path::new("out/election-2024.json")).unwrap();
+    return Snapshot::from_archived(&status, &aggregate);
+}
+
+/// The most recent archived run before `after` with both its status and its
+/// aggregate.
+fn previous_archived_run(after: &Snapshot) -> Option<Snapshot> {
+    let archive_directory =
+        get_option("archive-dir").unwrap_or(DEFAULT_ARCHIVE_DIRECTORY.to_string());
+    let archive_directory = Path::new(&archive_directory);
+    let statuses = archived_paths(archive_directory, &ArchiveKind::Status).ok()?;
+    let aggregates = archived_paths(archive_directory, &ArchiveKind::Aggregated).ok()?;
+    for (fetched_at, aggregate_path) in aggregates.into_iter().rev() {
+        if fetched_at >= after.fetched_at {
+            continue;
+        }
+        let status_path = match statuses
+            .iter()
+            .find(|(status_at, _)| *status_at == fetched_at)
+        {
+            Some((_, status_path)) => status_path,
+            None => continue,
+        };
+        let status: Status = read_archived(status_path).unwrap();
+        let aggregate: ArchivedAggregate = read_archived(&aggregate_path).unwrap();
+        return Some(Snapshot::from_archived(&status, &aggregate));
+    }
+    return None;
+}
@@ -1,135 +1,360 @@
-use election_2024::{ConstituencyStatus, Party, PartyName, Status};
-use rayon::prelude::*;
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use election_2024::archive::{write_to_archive, ArchiveKind, DEFAULT_ARCHIVE_DIRECTORY};
+use election_2024::constituencies::match_constituency;
+use election_2024::manifold::{
+    ManifoldClient, ManifoldClientConfig, Market, MarketAnswer, MarketDetailed, SnapshotMode,
+};
+use election_2024::parties::PartyAliases;
+use election_2024::trajectories::reconstruct_trajectory;
+use election_2024::{
+    get_option, AnswerTrajectory, ConstituencyStatus, ConstituencyTrajectory, MarketError,
+    MarketErrorKind, MarketMetadata, Party, PartyName, Status, Trajectories, UnparsedAnswer,
+    UnparsedAnswerMarket,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// A market resolved to a constituency, with the party each answer was parsed as.
+type MatchedMarket<'a> = (
+    &'a Market,
+    ConstituencyStatus,
+    Vec<(MarketAnswer, PartyName)>,
+);
+
+/// What to do with answers which don't match any party alias.
+enum UnknownParties {
+    /// Keep them as their own party.
+    Keep,
+    /// Count them as `Other`.
+    Other,
+    /// Stop without writing anything.
+    Fail,
+}
 
 fn main() {
+    let party_aliases = PartyAliases::load(get_option("party-aliases").as_deref());
+    let unknown_parties = get_unknown_parties();
+    let config = get_client_config();
+    let fetched_at = get_fetched_at(&config.snapshot);
+    let client = ManifoldClient::new(config);
+
     // the group which contains all the markets
     let group_id = "f763184a-51f4-4de2-a9df-d290134e6298";
-    let markets = get_all_markets_in_group(&group_id);
+    let markets = client
+        .get_all_markets_in_group(group_id)
+        .expect("could not list the markets in the group");
+    println!("Found {} markets in the group", markets.len());
 
     let good_markets = markets
         .iter()
         .filter(|market| {
             // this was a test market we don't want to include
-            if market.id == "u9745NJc14O05boS3wCl" {
-                return false;
-            } else {
-                return true;
-            }
+            return market.id != "u9745NJc14O05boS3wCl";
         })
         .collect::<Vec<&Market>>();
 
-    let constituencies = good_markets
-        .par_iter()
-        .map(|market| {
-            let market_detailed = get_market_answer_probabiities(&market.id);
-            let constituency_name = extract_constituency_name(&market.question);
+    let market_ids: Vec<String> = good_markets
+        .iter()
+        .map(|market| market.id.clone())
+        .collect();
+    let mut market_errors: Vec<MarketError> = Vec::new();
+    let mut constituencies: Vec<MatchedMarket> = Vec::new();
+    let mut unparsed_answers: BTreeMap<String, Vec<UnparsedAnswerMarket>> = BTreeMap::new();
+    for (market, (_, result)) in good_markets.iter().zip(client.get_markets(&market_ids)) {
+        let market_error = |kind: MarketErrorKind| MarketError {
+            market_id: market.id.clone(),
+            question: market.question.clone(),
+            kind,
+        };
+        let market_detailed = match result {
+            Ok(market_detailed) => market_detailed,
+            Err(error) => {
+                market_errors.push(market_error(MarketErrorKind::FetchFailed {
+                    message: error.to_string(),
+                }));
+                continue;
+            }
+        };
+        let constituency_name = extract_constituency_name(&market.question);
+        let reference = match match_constituency(&constituency_name) {
+            Some(reference) => reference,
+            None => {
+                market_errors.push(market_error(MarketErrorKind::UnmatchedConstituency {
+                    name: constituency_name,
+                }));
+                continue;
+            }
+        };
 
-            let mut parties: Vec<Party> = Vec::new();
-            for answer in &market_detailed.answers {
-                let party = Party {
-                    name: parse_party_name(&answer.text),
+        let mut parties: Vec<Party> = Vec::new();
+        let mut answer_parties: Vec<(MarketAnswer, PartyName)> = Vec::new();
+        for answer in &market_detailed.answers {
+            let mut name = party_aliases.parse(&answer.text);
+            if let PartyName::Unparsed(text) = &name {
+                unparsed_answers
+                    .entry(text.clone())
+                    .or_default()
+                    .push(UnparsedAnswerMarket {
+                        market_id: market.id.clone(),
+                        question: market.question.clone(),
+                    });
+                if let UnknownParties::Other = unknown_parties {
+                    name = PartyName::Other;
+                }
+            }
+            answer_parties.push((answer.clone(), name.clone()));
+            // several answers can end up as the same party, e.g. when
+            // folding unknown answers into `Other` or with two aliases of one
+            // party, so they're merged into one entry
+            match parties.iter_mut().find(|party| party.name == name) {
+                Some(party) => party.probability += answer.probability,
+                None => parties.push(Party {
+                    name,
                     probability: answer.probability,
-                };
-                parties.push(party);
+                }),
             }
+        }
 
-            let constituency = ConstituencyStatus {
-                constituency: constituency_name,
-                parties: parties,
-                manifold_url: market_detailed.url.clone(),
-            };
+        let constituency = ConstituencyStatus {
+            ons_code: reference.ons_code.clone(),
+            constituency: reference.name.clone(),
+            parties,
+            manifold_url: market_detailed.url.clone(),
+            market: get_market_metadata(&market_detailed),
+        };
 
-            return constituency;
-        })
+        constituencies.push((market, constituency, answer_parties));
+    }
+
+    // if two markets claim the same constituency we can't tell which is right,
+    // so leave them all out
+    let mut markets_per_code: HashMap<String, usize> = HashMap::new();
+    for (_, constituency, _) in &constituencies {
+        *markets_per_code
+            .entry(constituency.ons_code.clone())
+            .or_insert(0) += 1;
+    }
+    let mut unique_constituencies = Vec::new();
+    let mut unique_markets = Vec::new();
+    for (market, constituency, answer_parties) in constituencies {
+        if markets_per_code[&constituency.ons_code] > 1 {
+            market_errors.push(MarketError {
+                market_id: market.id.clone(),
+                question: market.question.clone(),
+                kind: MarketErrorKind::DuplicateConstituency {
+                    ons_code: constituency.ons_code,
+                },
+            });
+        } else {
+            unique_markets.push((market, constituency.clone(), answer_parties));
+            unique_constituencies.push(constituency);
+        }
+    }
+
+    let unparsed_answers: Vec<UnparsedAnswer> = unparsed_answers
+        .into_iter()
+        .map(|(text, markets)| UnparsedAnswer { text, markets })
         .collect();
+    if !unparsed_answers.is_empty() {
+        eprintln!("Could not parse {} answers:", unparsed_answers.len());
+        for unparsed_answer in &unparsed_answers {
+            eprintln!("  `{}` in:", unparsed_answer.text);
+            for market in &unparsed_answer.markets {
+                eprintln!("    {} ({})", market.question, market.market_id);
+            }
+        }
+        if let UnknownParties::Fail = unknown_parties {
+            panic!("some answers are not a known party, add them to the party aliases");
+        }
+    }
 
     let status = Status {
-        fetched_at: chrono::Utc::now(),
-        constituencies: constituencies,
+        fetched_at,
+        constituencies: unique_constituencies,
+        market_errors,
+        unparsed_answers,
     };
 
     // output the stats
     let output = serde_json::to_string(&status).unwrap();
     std::fs::write("out/constituencies.json", output).unwrap();
-}
 
-fn parse_party_name(party_name: &str) -> PartyName {
-    let trimmed_name = party_name.trim();
-    return match trimmed_name {
-        "Conservative" => PartyName::Conservatives,
-        "Liberal Democrat" => PartyName::LiberalDemocrats,
-        "Lib Dem" => PartyName::LiberalDemocrats,
-        "Labour" => PartyName::Labour,
-        "Conservatives" => PartyName::Conservatives,
-        "Liberal Democrats" => PartyName::LiberalDemocrats,
-        "Scottish National Party" => PartyName::SNP,
-        "Sinn Féin" => PartyName::SinnFein,
-        "Democratic Unionist Party" => PartyName::DUP,
-        "Plaid Cymru" => PartyName::PlaidCymru,
-        "Alliance" => PartyName::Alliance,
-        "Social Democratic and Labour Party" => PartyName::SDLP,
-        "Green" => PartyName::Green,
-        "Workers Party of Britain" => PartyName::WorkersPartyOfBritain,
-        "Workers Party" => PartyName::WorkersPartyOfBritain,
-        "Reform" => PartyName::Reform,
-        "Reform UK" => PartyName::Reform,
-        "Other" => PartyName::Other,
-        _ => {
-            if trimmed_name.starts_with("Independent") {
-                PartyName::Independent
-            } else {
-                PartyName::Unparsed(trimmed_name.to_string())
+    // and keep a copy in the archive, so the history isn't lost
+    let archive_directory =
+        get_option("archive-dir").unwrap_or(DEFAULT_ARCHIVE_DIRECTORY.to_string());
+    write_to_archive(
+        Path::new(&archive_directory),
+        &ArchiveKind::Status,
+        &status.fetched_at,
+        &status,
+    )
+    .unwrap();
+
+    if get_bet_history() {
+        let trajectories = get_trajectories(&client, &unique_markets, &status.fetched_at);
+        let output = serde_json::to_string(&trajectories).unwrap();
+        std::fs::write("out/trajectories.json", output).unwrap();
+    } else {
+        // don't leave an older run's trajectories to be rendered with this one
+        match std::fs::remove_file("out/trajectories.json") {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                panic!("could not remove out/trajectories.json: {}", error)
             }
+            _ => {}
         }
-    };
+    }
+
+    // report the markets we had to leave out rather than failing the whole run
+    if !status.market_errors.is_empty() {
+        eprintln!("Left out {} markets:", status.market_errors.len());
+        for market_error in &status.market_errors {
+            eprintln!(
+                "  {} ({}): {}",
+                market_error.question,
+                market_error.market_id,
+                market_error.kind.to_string()
+            );
+        }
+    }
 }
 
-fn extract_constituency_name(market_question: &str) -> String {
-    // question will be of the form `UK General Election: Which party will win in Altrincham and Sale West?`
-    // we want to extract `Altrincham and Sale West`
+/// When recording, save the time the snapshot was taken so that replaying it
+/// gives back exactly the same output.
+fn get_fetched_at(snapshot: &SnapshotMode) -> DateTime<Utc> {
+    return match snapshot {
+        SnapshotMode::Live => Utc::now(),
+        SnapshotMode::Record(directory) => {
+            let fetched_at = Utc::now();
+            std::fs::create_dir_all(directory).unwrap();
+            std::fs::write(directory.join("fetched_at.txt"), fetched_at.to_rfc3339()).unwrap();
+            fetched_at
+        }
+        SnapshotMode::Replay(directory) => {
+            let fetched_at = std::fs::read_to_string(directory.join("fetched_at.txt")).unwrap();
+            DateTime::parse_from_rfc3339(fetched_at.trim())
+                .unwrap()
+                .with_timezone(&Utc)
+        }
+    };
+}
 
-    let mut parts: Vec<&str> = market_question.split("Which party will win in ").collect();
-    if parts.len() < 2 {
-        // some markets omit the 'in'
-        parts = market_question.split("Which party will win ").collect();
+/// Rebuild every answer's probability over time from the market's bets. A
+/// market whose bets can't be fetched is reported and left out.
+fn get_trajectories(
+    client: &ManifoldClient,
+    markets: &[MatchedMarket],
+    fetched_at: &DateTime<Utc>,
+) -> Trajectories {
+    let market_ids: Vec<String> = markets
+        .iter()
+        .map(|(market, _, _)| market.id.clone())
+        .collect();
+    let mut constituencies = Vec::new();
+    for ((market, constituency, answer_parties), (_, result)) in
+        markets.iter().zip(client.get_bets_for_markets(&market_ids))
+    {
+        let bets = match result {
+            Ok(bets) => bets,
+            Err(error) => {
+                eprintln!(
+                    "Could not fetch the bets on {} ({}): {}",
+                    market.question, market.id, error
+                );
+                continue;
+            }
+        };
+        let answers = answer_parties
+            .iter()
+            .map(|(answer, party)| AnswerTrajectory {
+                party: party.clone(),
+                answer: answer.text.clone(),
+                points: reconstruct_trajectory(&bets, &answer.id, answer.probability, fetched_at),
+            })
+            .collect();
+        constituencies.push(ConstituencyTrajectory {
+            ons_code: constituency.ons_code.clone(),
+            constituency: constituency.constituency.clone(),
+            answers,
+        });
     }
-    let constituency_name = parts[1].split("?").collect::<Vec<&str>>()[0];
-    return constituency_name.to_string();
+    return Trajectories {
+        fetched_at: *fetched_at,
+        constituencies,
+    };
 }
 
-#[derive(Debug, Deserialize, Clone)]
-struct Market {
-    id: String,
-    question: String,
+fn get_bet_history() -> bool {
+    let bet_history = get_option("bet-history").unwrap_or("false".to_string());
+    return match bet_history.as_str() {
+        "true" => true,
+        "false" => false,
+        _ => panic!("bet-history must be true or false"),
+    };
 }
 
-fn get_all_markets_in_group(group_id: &str) -> Vec<Market> {
-    // include 1000 markets per page
-    let url = format!(
-        "https://api.manifold.markets/v0/markets?groupId={}&limit=1000",
-        group_id
-    );
-    let response = reqwest::blocking::get(&url).unwrap();
-    let markets: Vec<Market> = response.json().unwrap();
-    return markets;
+fn get_market_metadata(market: &MarketDetailed) -> MarketMetadata {
+    let to_date_time = |millis: Option<i64>| millis.and_then(DateTime::from_timestamp_millis);
+    return MarketMetadata {
+        volume: market.volume,
+        total_liquidity: market.total_liquidity,
+        unique_bettors: market.unique_bettor_count,
+        created_time: to_date_time(market.created_time),
+        close_time: to_date_time(market.close_time),
+        last_bet_time: to_date_time(market.last_bet_time),
+        is_resolved: market.is_resolved,
+        resolution: market.resolution.clone(),
+    };
 }
 
-#[derive(Debug, Deserialize)]
-struct MarketDetailed {
-    answers: Vec<MarketAnswer>,
-    url: String,
+fn get_unknown_parties() -> UnknownParties {
+    let unknown_parties = get_option("unknown-parties").unwrap_or("keep".to_string());
+    return match unknown_parties.as_str() {
+        "keep" => UnknownParties::Keep,
+        "other" => UnknownParties::Other,
+        "fail" => UnknownParties::Fail,
+        _ => panic!("Unknown value for unknown-parties: {}", unknown_parties),
+    };
 }
 
-#[derive(Debug, Deserialize)]
-struct MarketAnswer {
-    text: String,
-    probability: f64,
+fn get_client_config() -> ManifoldClientConfig {
+    let default = ManifoldClientConfig::default();
+    let parse = |name: &str| {
+        get_option(name).map(|value| {
+            value
+                .parse::<usize>()
+                .unwrap_or_else(|_| panic!("{} must be a number", name))
+        })
+    };
+    return ManifoldClientConfig {
+        base_url: get_option("manifold-base-url").unwrap_or(default.base_url.clone()),
+        concurrency: parse("concurrency").unwrap_or(default.concurrency),
+        page_size: parse("page-size").unwrap_or(default.page_size),
+        max_retries: parse("max-retries")
+            .map(|retries| retries as u32)
+            .unwrap_or(default.max_retries),
+        snapshot: match (get_option("record"), get_option("replay")) {
+            (None, None) => SnapshotMode::Live,
+            (Some(directory), None) => SnapshotMode::Record(directory.into()),
+            (None, Some(directory)) => SnapshotMode::Replay(directory.into()),
+            (Some(_), Some(_)) => panic!("can't both record and replay a snapshot"),
+        },
+        ..default
+    };
 }
 
-fn get_market_answer_probabiities(market_id: &str) -> MarketDetailed {
-    let url = format!("https://api.manifold.markets/v0/market/{}", market_id);
-    let response = reqwest::blocking::get(&url).unwrap();
-    let market_detailed: MarketDetailed = response.json().unwrap();
-    return market_detailed;
+fn extract_constituency_name(market_question: &str) -> String {
+    // question will be of the form `UK General Election: Which party will win in Altrincham and Sale West?`
+    // we want to extract `Altrincham and Sale West`
+
+    let mut parts: Vec<&str> = market_question.split("Which party will win in ").collect();
+    if parts.len() < 2 {
+        // some markets omit the 'in'
+        parts = market_question.split("Which party will win ").collect();
+    }
+    let constituency_name = parts
+        .get(1)
+        .unwrap_or(&market_question)
+        .split("?")
+        .collect::<Vec<&str>>()[0];
+    return constituency_name.to_string();
 }
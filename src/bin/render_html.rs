@@ -1,21 +1,57 @@
 #![recursion_limit = "512"]
 
+use chrono::{DateTime, Duration, Utc};
+use election_2024::trajectories::{biggest_movers, probability_at};
 use election_2024::{
-    AggregatedStats, ConstituencyAggregated, MonteCarloSummarySimple, Party, PartyName,
-    NUMBER_OF_SIMULATIONS,
+    inline_svg, AggregatedStats, AnswerTrajectory, ChangeReport, ConstituencyAggregated,
+    ConstituencyTrajectory, CoverageReport, ImputationStrategy, MonteCarloSummarySimple, Mover,
+    NotionalChange, NotionalComparison, Party, PartyName, PivotalSeat, RegionBreakdown,
+    ResultsSummary, Scenarios, SeatTransition, SimulationMode, ThinMarketReliance, Trajectories,
 };
+use std::collections::HashMap;
+
+/// What the region selector calls the national totals.
+const UNITED_KINGDOM: &str = "United Kingdom";
+
+/// How many rows the seats that matter most table has.
+const NUMBER_OF_PIVOTAL_SEATS: usize = 20;
+
+/// How many rows each of the biggest movers tables has.
+const NUMBER_OF_MOVERS: usize = 10;
+
+const SPARKLINE_DAYS: i64 = 30;
+/// How many of the leading answers get a line in each sparkline.
+const SPARKLINE_ANSWERS: usize = 4;
+const SPARKLINE_POINTS: usize = 60;
 
 fn main() {
     let input = std::fs::read("out/election-2024.json").unwrap();
     let constituencies: AggregatedStats = serde_json::from_slice(&input).unwrap();
+    // only there if download_data was run with the bet history, and only
+    // used if it's from the same download
+    let trajectories: Option<Trajectories> = std::fs::read("out/trajectories.json")
+        .ok()
+        .map(|input| serde_json::from_slice::<Trajectories>(&input).unwrap())
+        .filter(|trajectories| trajectories.fetched_at == constituencies.fetched_at);
+    // only there if the diff binary was run, and only used if it compares
+    // against this aggregate
+    let changes: Option<ChangeReport> = std::fs::read("out/changes.json")
+        .ok()
+        .map(|input| serde_json::from_slice::<ChangeReport>(&input).unwrap())
+        .filter(|changes| changes.after_fetched_at == constituencies.fetched_at);
 
-    let html = render_html(&constituencies);
+    let html = render_html(&constituencies, trajectories.as_ref(), changes.as_ref());
     // save to file
     std::fs::write("out/index.html", html).unwrap();
     std::fs::copy("src/sort.js", "out/sort.js").unwrap();
+    std::fs::copy("src/regions.js", "out/regions.js").unwrap();
 }
 
-fn render_html(constituencies: &AggregatedStats) -> String {
+fn render_html(
+    constituencies: &AggregatedStats,
+    trajectories: Option<&Trajectories>,
+    changes: Option<&ChangeReport>,
+) -> String {
     let tree = html::root::Html::builder()
         .lang("en")
         .head(|head| {
@@ -47,38 +83,196 @@ fn render_html(constituencies: &AggregatedStats) -> String {
             body.push(fetched_at.build());
 
             let mut github_link = html::text_content::Paragraph::builder();
-            github_link.text(format!(
-                "Open source at "
-            ));
+            github_link.text("Open source at ");
             github_link.push(html::inline_text::Anchor::builder().href("https://github.com/onthestairs/manifold-election-2024").text("Github").build());
             github_link.text(".");
             body.push(github_link.build());
 
             body.push(html::text_content::ThematicBreak::builder().build());
 
+            if constituencies.results.declared_seats > 0 {
+                let mut results_heading = html::content::Heading2::builder();
+                results_heading.text("Results");
+                body.push(results_heading.build());
+                let mut results_paragraph = html::text_content::Paragraph::builder();
+                results_paragraph.text(format!(
+                    "{} seats have declared and {} are still to come. Declared seats are fixed to their winner in the simulations, so the projections are for the final result.",
+                    constituencies.results.declared_seats,
+                    constituencies.results.undeclared_seats
+                ));
+                body.push(results_paragraph.build());
+                body.push(make_results_table(
+                    &constituencies.results,
+                    &constituencies.monte_carlo_summary,
+                ));
+
+                body.push(html::text_content::ThematicBreak::builder().build());
+            }
+
+            body.push(make_region_selector(&constituencies.regions));
+
             let mut summary_heading = html::content::Heading2::builder();
             summary_heading.text("Monte Carlo simulation results");
             body.push(summary_heading.build());
             let mut summary_paragraph = html::text_content::Paragraph::builder();
             summary_paragraph.text(
-                format!("The following table shows the result of a Monte Carlo simulation. A simulated election is run {} times. For each constituency, a party is returned randomly based on the implied probabilities of the market. The median is the middle number of seats won by that party across all the simulations. The majority percent shows how many times in the simulation the given party wins a majority (>325 seats). ", NUMBER_OF_SIMULATIONS),
+                format!("The following table shows the result of a Monte Carlo simulation. A simulated election is run {} times. For each constituency, a party is returned randomly based on the implied probabilities of the market. The median is the middle number of seats won by that party across all the simulations. The majority percent shows how many times in the simulation the given party wins a majority (>325 seats), or more than half the seats when a region is selected. ", constituencies.number_of_simulations),
             );
             body.push(summary_paragraph.build());
-            let summary_table = make_summary_table(&constituencies.monte_carlo_summary);
-            body.push(summary_table);
+            if let SimulationMode::Correlated { national_shock, nation_shock } = constituencies.simulation_mode {
+                let mut correlation_paragraph = html::text_content::Paragraph::builder();
+                correlation_paragraph.text(
+                    format!("Polling errors move every seat together, so each simulated election also draws a shared swing for every party (a log-odds shock with standard deviation {:.2}), plus an extra swing for Scotland, Wales and Northern Ireland (standard deviation {:.2}). These are applied to every constituency's probabilities before a winner is picked.", national_shock, nation_shock),
+                );
+                body.push(correlation_paragraph.build());
+            }
+            if let SimulationMode::Exact = constituencies.simulation_mode {
+                let mut exact_paragraph = html::text_content::Paragraph::builder();
+                exact_paragraph.text("The seat totals and majority percentages below are worked out exactly from every constituency's probabilities rather than sampled, so there is no simulation noise. The scenarios further down still come from the simulations.");
+                body.push(exact_paragraph.build());
+            }
+            // only worth a column if there is a baseline to compare against
+            let notional_changes = if constituencies.notional_2019.seats_with_notional > 0 {
+                Some(constituencies.notional_2019.changes.as_slice())
+            } else {
+                None
+            };
+            let summary_tables = make_region_tables(
+                make_summary_table(
+                    &constituencies.monte_carlo_summary,
+                    notional_changes,
+                ),
+                constituencies.regions.iter().map(|region| {
+                    (region.region.clone(), make_summary_table(&region.monte_carlo_summary, None))
+                }),
+            );
+            body.push(summary_tables);
+
+            let mut distribution_heading = html::content::Heading3::builder();
+            distribution_heading.text("Seat distributions");
+            body.push(distribution_heading.build());
+            let mut distribution_paragraph = html::text_content::Paragraph::builder();
+            distribution_paragraph.text("How often each party won each number of seats across the simulations, with the seat count at the listed percentiles.");
+            body.push(distribution_paragraph.build());
+            body.push(make_seat_distribution_charts(&constituencies.monte_carlo_summary));
+
+            if let Some(baseline_summary) = &constituencies.baseline_monte_carlo_summary {
+                let mut baseline_heading = html::content::Heading3::builder();
+                baseline_heading.text("Independent baseline");
+                body.push(baseline_heading.build());
+                let mut baseline_paragraph = html::text_content::Paragraph::builder();
+                baseline_paragraph.text("For comparison, the same simulation with every constituency drawn independently.");
+                body.push(baseline_paragraph.build());
+                body.push(make_summary_table(baseline_summary, None));
+            }
 
             body.push(html::text_content::ThematicBreak::builder().build());
 
+            let mut scenarios_heading = html::content::Heading2::builder();
+            scenarios_heading.text("Scenarios");
+            body.push(scenarios_heading.build());
+            let mut scenarios_paragraph = html::text_content::Paragraph::builder();
+            scenarios_paragraph.text("How often each outcome happened across the simulated parliaments. A working majority ignores the Speaker and any Sinn Féin MPs, who don't take their seats.");
+            body.push(scenarios_paragraph.build());
+            body.push(make_scenarios_table(&constituencies.scenarios));
+
+            body.push(html::text_content::ThematicBreak::builder().build());
+
+            // no party won a majority in any simulation if this is empty
+            if !constituencies.pivotal_seats.is_empty() {
+                let mut pivotal_heading = html::content::Heading2::builder();
+                pivotal_heading.text("Seats that matter most");
+                body.push(pivotal_heading.build());
+                let mut pivotal_paragraph = html::text_content::Paragraph::builder();
+                pivotal_paragraph.text("Order a party's seats from its most to least likely win. In each simulation where the party wins a majority, the tipping point is the seat which takes it to 326. These are the seats which were most often the tipping point, with the correlation between the party winning the seat and winning a majority.");
+                body.push(pivotal_paragraph.build());
+                body.push(make_pivotal_seats_table(&constituencies.pivotal_seats));
+
+                body.push(html::text_content::ThematicBreak::builder().build());
+            }
+
+            if constituencies.notional_2019.seats_with_notional > 0 {
+                body.push(make_notional_section(&constituencies.notional_2019));
+
+                body.push(html::text_content::ThematicBreak::builder().build());
+            }
+
             let mut summary_heading = html::content::Heading2::builder();
             summary_heading.text("Seat favourites");
             body.push(summary_heading.build());
 
-            let stats_table = make_stats_table(&constituencies.winning_constituencies);
-            body.push(stats_table);
+            let stats_tables = make_region_tables(
+                make_stats_table(&constituencies.winning_constituencies),
+                constituencies.regions.iter().map(|region| {
+                    (region.region.clone(), make_stats_table(&region.winning_constituencies))
+                }),
+            );
+            body.push(stats_tables);
+
+            body.push(html::text_content::ThematicBreak::builder().build());
+
+            let flagged_count = constituencies
+                .constituencies
+                .iter()
+                .filter(|constituency| constituency.overround_flagged)
+                .count();
+            let mut normalisation_paragraph = html::text_content::Paragraph::builder();
+            normalisation_paragraph.text(format!(
+                "Market probabilities don't always sum to 100%, so they are normalised ({}) before use. {} markets summed to more than {:.0} points away from 100% and are marked with ⚠️.",
+                constituencies.normalisation.to_string().to_lowercase(),
+                flagged_count,
+                constituencies.overround_threshold * 100.0
+            ));
+            body.push(normalisation_paragraph.build());
+
+            body.push(make_coverage_paragraph(&constituencies.coverage));
 
             body.push(html::text_content::ThematicBreak::builder().build());
 
-            let constituency_tables = make_constituency_tables(&constituencies.constituencies);
+            let mut thin_markets_heading = html::content::Heading2::builder();
+            thin_markets_heading.text("Thin markets");
+            body.push(thin_markets_heading.build());
+            let thin_market_count = constituencies
+                .constituencies
+                .iter()
+                .filter(|constituency| constituency.thin_market)
+                .count();
+            let mut thin_markets_paragraph = html::text_content::Paragraph::builder();
+            thin_markets_paragraph.text(format!(
+                "Each market gets a quality score from 0 to 1 from its volume, number of traders and how recently it was traded. {} seats score below {:.2} (or have no market) and are marked with 🧊. Thin market adjustment before simulating: {}. The table shows how much of each party's expected seat total comes from those seats.",
+                thin_market_count,
+                constituencies.quality_threshold,
+                constituencies.thin_market_adjustment.to_string().to_lowercase()
+            ));
+            body.push(thin_markets_paragraph.build());
+            body.push(make_thin_market_reliance_table(&constituencies.thin_market_reliance));
+
+            if let Some(changes) = changes {
+                body.push(html::text_content::ThematicBreak::builder().build());
+                body.push(make_changes_section(changes));
+            }
+
+            if let Some(trajectories) = trajectories {
+                body.push(html::text_content::ThematicBreak::builder().build());
+
+                let mut movers_heading = html::content::Heading2::builder();
+                movers_heading.text("Biggest movers");
+                body.push(movers_heading.build());
+                for (title, duration) in [
+                    ("Last 24 hours", Duration::hours(24)),
+                    ("Last 7 days", Duration::days(7)),
+                ] {
+                    let mut movers_heading = html::content::Heading3::builder();
+                    movers_heading.text(title);
+                    body.push(movers_heading.build());
+                    let since = trajectories.fetched_at - duration;
+                    let movers = biggest_movers(trajectories, &since, NUMBER_OF_MOVERS);
+                    body.push(make_movers_table(&movers));
+                }
+            }
+
+            let constituency_tables =
+                make_constituency_tables(&constituencies.constituencies, trajectories);
             body.push(constituency_tables);
 
             body.script(|script| {
@@ -86,6 +280,11 @@ fn render_html(constituencies: &AggregatedStats) -> String {
                 script.type_("text/javascript");
                 return script;
             });
+            body.script(|script| {
+                script.src("regions.js");
+                script.type_("text/javascript");
+                return script;
+            });
 
             return body;
         })
@@ -94,8 +293,63 @@ fn render_html(constituencies: &AggregatedStats) -> String {
     return tree.to_string();
 }
 
+fn make_region_selector(regions: &[RegionBreakdown]) -> html::text_content::Paragraph {
+    let mut paragraph = html::text_content::Paragraph::builder();
+    paragraph.push(
+        html::forms::Label::builder()
+            .for_("region-selector")
+            .text("Show the tables below for: ")
+            .build(),
+    );
+    let mut select = html::forms::Select::builder();
+    select.id("region-selector");
+    select.option(|option| {
+        option
+            .value(UNITED_KINGDOM)
+            .text(UNITED_KINGDOM)
+            .selected(true);
+        return option;
+    });
+    for region in regions {
+        select.option(|option| {
+            option
+                .value(region.region.clone())
+                .text(format!("{} ({} seats)", region.region, region.seats));
+            return option;
+        });
+    }
+    paragraph.push(select.build());
+    return paragraph.build();
+}
+
+/// One table per region, with only the national one shown until another
+/// region is picked in the selector, see `regions.js`.
+fn make_region_tables(
+    national_table: html::tables::Table,
+    region_tables: impl Iterator<Item = (String, html::tables::Table)>,
+) -> html::text_content::Division {
+    let mut division = html::text_content::Division::builder();
+    division.division(|national_division| {
+        national_division
+            .data("region", UNITED_KINGDOM)
+            .push(national_table);
+        return national_division;
+    });
+    for (region, table) in region_tables {
+        division.division(|region_division| {
+            region_division
+                .data("region", region)
+                .style("display: none;")
+                .push(table);
+            return region_division;
+        });
+    }
+    return division.build();
+}
+
 fn make_constituency_tables(
-    constituencies: &Vec<ConstituencyAggregated>,
+    constituencies: &[ConstituencyAggregated],
+    trajectories: Option<&Trajectories>,
 ) -> html::text_content::Division {
     let mut outer_division = html::text_content::Division::builder();
     let mut heading = html::content::Heading2::builder();
@@ -142,6 +396,10 @@ fn make_constituency_tables(
         "thirdPlaceProbability",
         true,
     ));
+    sorters_list.push(make_sorter("Market total", "overround", true));
+    sorters_list.push(make_sorter("Volume", "volume", true));
+    sorters_list.push(make_sorter("Market quality", "marketQuality", true));
+    sorters_list.push(make_sorter("Traders", "uniqueBettors", true));
     sorters.push(sorters_list.build());
     outer_division.push(sorters.build());
 
@@ -155,8 +413,22 @@ fn make_constituency_tables(
         .iter()
         .collect::<Vec<&ConstituencyAggregated>>();
     sorted_constituencies.sort_by(|a, b| a.constituency.cmp(&b.constituency));
+    let trajectories_by_code: HashMap<&str, &ConstituencyTrajectory> = trajectories
+        .map(|trajectories| {
+            trajectories
+                .constituencies
+                .iter()
+                .map(|trajectory| (trajectory.ons_code.as_str(), trajectory))
+                .collect()
+        })
+        .unwrap_or_default();
     for constituency in &sorted_constituencies {
-        let table = make_constituency_table(constituency);
+        let sparkline = trajectories.and_then(|trajectories| {
+            trajectories_by_code
+                .get(constituency.ons_code.as_str())
+                .map(|trajectory| make_sparkline(trajectory, &trajectories.fetched_at))
+        });
+        let table = make_constituency_table(constituency, sparkline);
         division.push(table);
     }
 
@@ -165,7 +437,260 @@ fn make_constituency_tables(
     return outer_division.build();
 }
 
-fn make_constituency_table(constituency: &ConstituencyAggregated) -> html::text_content::Division {
+/// A small chart of the leading answers' probabilities over the last month,
+/// as an inline SVG.
+fn make_sparkline(
+    trajectory: &ConstituencyTrajectory,
+    fetched_at: &DateTime<Utc>,
+) -> html::text_content::Division {
+    let width = 200.0;
+    let height = 40.0;
+    let start = *fetched_at - Duration::days(SPARKLINE_DAYS);
+    let mut answers: Vec<&AnswerTrajectory> = trajectory.answers.iter().collect();
+    let current_probability =
+        |answer: &AnswerTrajectory| probability_at(&answer.points, fetched_at).unwrap_or(0.0);
+    answers.sort_by(|a, b| {
+        current_probability(b)
+            .partial_cmp(&current_probability(a))
+            .unwrap()
+    });
+
+    let mut svg = format!(
+        "<svg xmlns='http://www.w3.org/2000/svg' width='{}' height='{}'>",
+        width, height
+    );
+    // shown as a tooltip
+    let label = format!("Probabilities over the last {} days", SPARKLINE_DAYS);
+    svg.push_str(&format!("<title>{}</title>", label));
+    for answer in answers.iter().take(SPARKLINE_ANSWERS) {
+        let points: Vec<String> = (0..=SPARKLINE_POINTS)
+            .map(|i| {
+                let fraction = i as f64 / SPARKLINE_POINTS as f64;
+                let time = start
+                    + Duration::seconds(
+                        (fraction * Duration::days(SPARKLINE_DAYS).num_seconds() as f64) as i64,
+                    );
+                let probability = probability_at(&answer.points, &time).unwrap_or(0.0);
+                return format!(
+                    "{:.1},{:.1}",
+                    fraction * width,
+                    (1.0 - probability) * height
+                );
+            })
+            .collect();
+        svg.push_str(&format!(
+            "<polyline fill='none' stroke='{}' stroke-width='1.5' points='{}'/>",
+            answer.party.colour(),
+            points.join(" ")
+        ));
+    }
+    svg.push_str("</svg>");
+
+    return inline_svg(&svg, &label);
+}
+
+fn make_changes_section(changes: &ChangeReport) -> html::text_content::Division {
+    let mut division = html::text_content::Division::builder();
+    let mut heading = html::content::Heading2::builder();
+    heading.text("What changed");
+    division.push(heading.build());
+
+    let mut paragraph = html::text_content::Paragraph::builder();
+    paragraph.text(format!(
+        "Since {} UTC: {} seats changed favourite, {} markets were added and {} removed.",
+        changes.before_fetched_at.format("%Y-%m-%d %H:%M:%S"),
+        changes.flipped_seats.len(),
+        changes.added_markets.len(),
+        changes.removed_markets.len()
+    ));
+    division.push(paragraph.build());
+
+    if !changes.flipped_seats.is_empty() {
+        let mut table = html::tables::Table::builder();
+        table.table_row(|row| {
+            row.table_header(|header| {
+                header.text("Constituency");
+                return header;
+            });
+            row.table_header(|header| {
+                header.text("Favourite before");
+                return header;
+            });
+            row.table_header(|header| {
+                header.text("Favourite now");
+                return header;
+            });
+            return row;
+        });
+        for seat in &changes.flipped_seats {
+            let row = html::tables::TableRow::builder()
+                .table_cell(|data| {
+                    data.text(seat.constituency.clone());
+                    return data;
+                })
+                .table_cell(|data| {
+                    data.push(make_party_swatch(&seat.favourite_before));
+                    data.text(format!(
+                        "{} ({:.1}%)",
+                        seat.favourite_before.to_string(),
+                        seat.probability_before * 100.0
+                    ));
+                    return data;
+                })
+                .table_cell(|data| {
+                    data.push(make_party_swatch(&seat.favourite_after));
+                    data.text(format!(
+                        "{} ({:.1}%)",
+                        seat.favourite_after.to_string(),
+                        seat.probability_after * 100.0
+                    ));
+                    return data;
+                })
+                .build();
+            table.push(row);
+        }
+        division.push(table.build());
+    }
+
+    if !changes.simulation_changes.is_empty() {
+        let format_median = |median: Option<i32>| {
+            median
+                .map(|median| median.to_string())
+                .unwrap_or("-".to_string())
+        };
+        let format_percentage = |percentage: Option<f64>| {
+            percentage
+                .map(|percentage| format!("{:.2}%", percentage * 100.0))
+                .unwrap_or("-".to_string())
+        };
+        let mut table = html::tables::Table::builder();
+        table.table_row(|row| {
+            row.table_header(|header| {
+                header.text("Party");
+                return header;
+            });
+            row.table_header(|header| {
+                header.text("Median seats");
+                return header;
+            });
+            row.table_header(|header| {
+                header.text("Majority percentage");
+                return header;
+            });
+            return row;
+        });
+        for change in &changes.simulation_changes {
+            let row = html::tables::TableRow::builder()
+                .table_cell(|data| {
+                    data.push(make_party_swatch(&change.party));
+                    data.text(change.party.to_string());
+                    return data;
+                })
+                .table_cell(|data| {
+                    data.text(format!(
+                        "{} → {}",
+                        format_median(change.median_before),
+                        format_median(change.median_after)
+                    ));
+                    return data;
+                })
+                .table_cell(|data| {
+                    data.text(format!(
+                        "{} → {}",
+                        format_percentage(change.majority_percentage_before),
+                        format_percentage(change.majority_percentage_after)
+                    ));
+                    return data;
+                })
+                .build();
+            table.push(row);
+        }
+        division.push(table.build());
+    }
+
+    let movers: Vec<Mover> = changes
+        .probability_changes
+        .iter()
+        .map(|change| Mover {
+            ons_code: change.ons_code.clone(),
+            constituency: change.constituency.clone(),
+            party: change.party.clone(),
+            probability_before: change.probability_before,
+            probability_now: change.probability_after,
+        })
+        .collect();
+    let mut movers_heading = html::content::Heading3::builder();
+    movers_heading.text("Largest probability changes");
+    division.push(movers_heading.build());
+    division.push(make_movers_table(&movers));
+
+    for (title, markets) in [
+        ("Markets added", &changes.added_markets),
+        ("Markets removed", &changes.removed_markets),
+    ] {
+        if markets.is_empty() {
+            continue;
+        }
+        let names: Vec<&str> = markets
+            .iter()
+            .map(|market| market.constituency.as_str())
+            .collect();
+        let mut paragraph = html::text_content::Paragraph::builder();
+        paragraph.text(format!("{}: {}.", title, names.join(", ")));
+        division.push(paragraph.build());
+    }
+
+    return division.build();
+}
+
+fn make_movers_table(movers: &[Mover]) -> html::tables::Table {
+    let mut table = html::tables::Table::builder();
+    table.table_row(|row| {
+        row.table_header(|header| {
+            header.text("Constituency");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("Party");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("Change");
+            return header;
+        });
+        return row;
+    });
+
+    for mover in movers {
+        let row = html::tables::TableRow::builder()
+            .table_cell(|data| {
+                data.text(mover.constituency.clone());
+                return data;
+            })
+            .table_cell(|data| {
+                data.push(make_party_swatch(&mover.party));
+                data.text(mover.party.to_string());
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(format!(
+                    "{:.1}% → {:.1}% ({:+.1})",
+                    mover.probability_before * 100.0,
+                    mover.probability_now * 100.0,
+                    (mover.probability_now - mover.probability_before) * 100.0
+                ));
+                return data;
+            })
+            .build();
+        table.push(row);
+    }
+    return table.build();
+}
+
+fn make_constituency_table(
+    constituency: &ConstituencyAggregated,
+    sparkline: Option<html::text_content::Division>,
+) -> html::text_content::Division {
     let mut division = html::text_content::Division::builder();
 
     let mut reverse_sorted_parties = constituency.parties.iter().collect::<Vec<&Party>>();
@@ -237,6 +762,14 @@ fn make_constituency_table(constituency: &ConstituencyAggregated) -> html::text_
             .unwrap_or("".to_string()),
     );
 
+    division.data("overround", constituency.overround.to_string());
+    division.data("volume", constituency.market.volume.to_string());
+    division.data("market-quality", constituency.market_quality.to_string());
+    division.data(
+        "unique-bettors",
+        constituency.market.unique_bettors.to_string(),
+    );
+
     let labour_probability = constituency
         .parties
         .iter()
@@ -249,10 +782,21 @@ fn make_constituency_table(constituency: &ConstituencyAggregated) -> html::text_
     heading.text(constituency.constituency.clone());
     division.push(heading.build());
 
+    if let Some(winner) = &constituency.declared_winner {
+        let mut declared = html::text_content::Paragraph::builder();
+        declared.text(format!(
+            "✅ Declared: {} {}",
+            winner.to_string(),
+            winner.to_emoji()
+        ));
+        division.push(declared.build());
+    }
+
     let mut table = html::tables::Table::builder();
     for party in &reverse_sorted_parties {
         let row = html::tables::TableRow::builder()
             .table_cell(|cell| {
+                cell.push(make_party_swatch(&party.name));
                 cell.text(party.name.to_string());
                 cell.text(" ");
                 cell.text(party.name.to_emoji());
@@ -267,16 +811,207 @@ fn make_constituency_table(constituency: &ConstituencyAggregated) -> html::text_
     }
     division.push(table.build());
 
-    let mut link = html::inline_text::Anchor::builder();
-    link.href(constituency.manifold_url.clone());
-    link.target("_blank");
-    link.text("See market on Manifold");
-    division.push(link.build());
+    if let Some(sparkline) = sparkline {
+        division.push(sparkline);
+    }
+
+    let mut overround = html::text_content::Paragraph::builder();
+    overround.style("font-size: small;");
+    if constituency.imputed {
+        overround.text("❓ No market for this seat, the probabilities are imputed");
+    } else if constituency.overround_flagged {
+        overround.text(format!(
+            "⚠️ Market probabilities summed to {:.2}% before normalisation",
+            constituency.overround * 100.0
+        ));
+    } else {
+        overround.text(format!(
+            "Market total before normalisation: {:.2}%",
+            constituency.overround * 100.0
+        ));
+    }
+    division.push(overround.build());
+
+    if !constituency.imputed {
+        division.push(make_market_metadata_paragraph(constituency));
+    }
+
+    if !constituency.imputed {
+        let mut link = html::inline_text::Anchor::builder();
+        link.href(constituency.manifold_url.clone());
+        link.target("_blank");
+        link.text("See market on Manifold");
+        division.push(link.build());
+    }
 
     return division.build();
 }
 
-fn make_summary_table(summaries: &Vec<MonteCarloSummarySimple>) -> html::tables::Table {
+fn make_market_metadata_paragraph(
+    constituency: &ConstituencyAggregated,
+) -> html::text_content::Paragraph {
+    let market = &constituency.market;
+    let format_time = |time: &Option<DateTime<Utc>>| {
+        time.map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or("-".to_string())
+    };
+    let mut paragraph = html::text_content::Paragraph::builder();
+    paragraph.style("font-size: small;");
+    if constituency.thin_market {
+        paragraph.text(format!(
+            "🧊 Thin market, quality {:.2}",
+            constituency.market_quality
+        ));
+    } else {
+        paragraph.text(format!(
+            "Market quality: {:.2}",
+            constituency.market_quality
+        ));
+    }
+    paragraph.push(html::inline_text::LineBreak::builder().build());
+    paragraph.text(format!(
+        "Volume: Ṁ{:.0}, liquidity: Ṁ{:.0}, traders: {}",
+        market.volume, market.total_liquidity, market.unique_bettors
+    ));
+    paragraph.push(html::inline_text::LineBreak::builder().build());
+    paragraph.text(format!(
+        "Created: {}, last bet: {}, closes: {}",
+        format_time(&market.created_time),
+        format_time(&market.last_bet_time),
+        format_time(&market.close_time)
+    ));
+    if market.is_resolved {
+        paragraph.push(html::inline_text::LineBreak::builder().build());
+        paragraph.text(format!(
+            "Resolved: {}",
+            market.resolution.clone().unwrap_or("unknown".to_string())
+        ));
+    }
+    return paragraph.build();
+}
+
+fn make_coverage_paragraph(coverage: &CoverageReport) -> html::text_content::Paragraph {
+    let mut paragraph = html::text_content::Paragraph::builder();
+    paragraph.text(format!(
+        "Markets were found for {} of the {} seats.",
+        coverage.covered_seats, coverage.expected_seats
+    ));
+    if !coverage.missing.is_empty() {
+        let missing: Vec<&str> = coverage
+            .missing
+            .iter()
+            .map(|missing| missing.constituency.as_str())
+            .collect();
+        let imputation = match coverage.imputation {
+            ImputationStrategy::None => "They are left out of the simulations.".to_string(),
+            _ => format!(
+                "They are imputed ({}) and marked with ❓.",
+                coverage.imputation.to_string().to_lowercase()
+            ),
+        };
+        paragraph.text(format!(" Missing: {}. {}", missing.join(", "), imputation));
+    }
+    if !coverage.duplicated.is_empty() {
+        let duplicated: Vec<String> = coverage
+            .duplicated
+            .iter()
+            .map(|duplicated| format!("{} ({})", duplicated.constituency, duplicated.markets))
+            .collect();
+        paragraph.text(format!(
+            " More than one market, so none are used and they count as missing: {}.",
+            duplicated.join(", ")
+        ));
+    }
+    if !coverage.unidentified.is_empty() {
+        paragraph.text(format!(
+            " {} markets couldn't be matched to a seat.",
+            coverage.unidentified.len()
+        ));
+    }
+    return paragraph.build();
+}
+
+fn make_party_swatch(party: &PartyName) -> html::inline_text::Span {
+    return html::inline_text::Span::builder()
+        .style(format!(
+            "display: inline-block; width: 0.8em; height: 0.8em; margin-right: 0.3em; background: {};",
+            party.colour()
+        ))
+        .title(party.abbreviation())
+        .build();
+}
+
+fn make_results_table(
+    results: &ResultsSummary,
+    summaries: &[MonteCarloSummarySimple],
+) -> html::tables::Table {
+    let mut table = html::tables::Table::builder();
+    table.table_row(|row| {
+        row.table_header(|header| {
+            header.text("Party");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("Declared");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("Projected final seats");
+            header.push(html::inline_text::LineBreak::builder().build());
+            header.text("[5th - 95th percentile]");
+            return header;
+        });
+        return row;
+    });
+
+    // every party with a projection, then any that have won a seat without one
+    let mut parties: Vec<&PartyName> = summaries.iter().map(|summary| &summary.party).collect();
+    for (party, _) in &results.declared_by_party {
+        if !parties.contains(&party) {
+            parties.push(party);
+        }
+    }
+    for party in parties {
+        let declared = results
+            .declared_by_party
+            .iter()
+            .find(|(declared_party, _)| declared_party == party)
+            .map(|(_, seats)| *seats)
+            .unwrap_or(0);
+        let projection = summaries
+            .iter()
+            .find(|summary| summary.party == *party)
+            .map(|summary| {
+                format!(
+                    "{} [{} - {}]",
+                    summary.median, summary.lower_5th, summary.upper_95th
+                )
+            })
+            .unwrap_or("-".to_string());
+        let row = html::tables::TableRow::builder()
+            .table_cell(|data| {
+                data.push(make_party_swatch(party));
+                data.text(party.to_string());
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(declared.to_string());
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(projection);
+                return data;
+            })
+            .build();
+        table.push(row);
+    }
+    return table.build();
+}
+
+fn make_summary_table(
+    summaries: &[MonteCarloSummarySimple],
+    notional_changes: Option<&[NotionalChange]>,
+) -> html::tables::Table {
     let mut table = html::tables::Table::builder();
     table.table_row(|row| {
         row.table_header(|header| {
@@ -293,28 +1028,191 @@ fn make_summary_table(summaries: &Vec<MonteCarloSummarySimple>) -> html::tables:
             header.text("Majority percentage");
             return header;
         });
+        if notional_changes.is_some() {
+            row.table_header(|header| {
+                header.text("Net change vs 2019");
+                header.push(html::inline_text::LineBreak::builder().build());
+                header.text("[5th - 95th percentile]");
+                return header;
+            });
+        }
         return row;
     });
 
     for summary in summaries.iter() {
+        let mut row = html::tables::TableRow::builder();
+        row.table_cell(|data| {
+            data.push(make_party_swatch(&summary.party));
+            data.text(summary.party.to_string());
+            data.text(" ");
+            data.text(summary.party.to_emoji());
+            return data;
+        })
+        .table_cell(|data| {
+            data.text(summary.median.to_string());
+            data.text(" [");
+            data.text(summary.lower_5th.to_string());
+            data.text(" - ");
+            data.text(summary.upper_95th.to_string());
+            data.text("]");
+            return data;
+        })
+        .table_cell(|data| {
+            data.text(format!("{:.2}%", summary.majority_percentage * 100.0));
+            return data;
+        });
+        if let Some(notional_changes) = notional_changes {
+            let change = notional_changes
+                .iter()
+                .find(|change| change.party == summary.party);
+            row.table_cell(|data| {
+                if let Some(change) = change {
+                    data.text(format!(
+                        "{:+} [{:+} - {:+}]",
+                        change.net_change_median,
+                        change.net_change_lower_5th,
+                        change.net_change_upper_95th
+                    ));
+                }
+                return data;
+            });
+        }
+        table.push(row.build());
+    }
+    return table.build();
+}
+
+fn make_pivotal_seats_table(pivotal_seats: &[PivotalSeat]) -> html::tables::Table {
+    let mut table = html::tables::Table::builder();
+    table.table_row(|row| {
+        for title in [
+            "Constituency",
+            "Party",
+            "Tipping point",
+            "Correlation with majority",
+        ] {
+            row.table_header(|header| {
+                header.text(title);
+                return header;
+            });
+        }
+        return row;
+    });
+
+    for pivotal_seat in pivotal_seats.iter().take(NUMBER_OF_PIVOTAL_SEATS) {
         let row = html::tables::TableRow::builder()
             .table_cell(|data| {
-                data.text(summary.party.to_string());
-                data.text(" ");
-                data.text(summary.party.to_emoji());
+                data.text(pivotal_seat.constituency.clone());
+                return data;
+            })
+            .table_cell(|data| {
+                data.push(make_party_swatch(&pivotal_seat.party));
+                data.text(pivotal_seat.party.to_string());
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(format!(
+                    "{:.2}%",
+                    pivotal_seat.tipping_point_probability * 100.0
+                ));
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(format!("{:.2}", pivotal_seat.majority_correlation));
+                return data;
+            })
+            .build();
+        table.push(row);
+    }
+    return table.build();
+}
+
+fn make_notional_section(notional: &NotionalComparison) -> html::text_content::Division {
+    let mut division = html::text_content::Division::builder();
+    let mut heading = html::content::Heading2::builder();
+    heading.text("Gains and losses vs 2019");
+    division.push(heading.build());
+    let mut paragraph = html::text_content::Paragraph::builder();
+    paragraph.text(format!(
+        "Compared with the notional 2019 result, i.e. who would have won each seat had the 2019 election been fought on the new boundaries. {} seats have a notional winner. The favourite columns count the seats changing hands if every favourite won, the others are across the simulations, with the 5th to 95th percentile in brackets.",
+        notional.seats_with_notional
+    ));
+    division.push(paragraph.build());
+    division.push(make_notional_changes_table(&notional.changes));
+
+    let mut matrix_heading = html::content::Heading3::builder();
+    matrix_heading.text("Who takes seats from whom");
+    division.push(matrix_heading.build());
+    let mut matrix_paragraph = html::text_content::Paragraph::builder();
+    matrix_paragraph.text("Each row is the notional 2019 winner and each column the party taking the seat. The cells show the expected number of seats across the simulations, with the number if every favourite won in brackets.");
+    division.push(matrix_paragraph.build());
+    division.push(make_transitions_matrix(
+        &notional.changes,
+        &notional.transitions,
+    ));
+    return division.build();
+}
+
+fn make_notional_changes_table(changes: &[NotionalChange]) -> html::tables::Table {
+    let mut table = html::tables::Table::builder();
+    table.table_row(|row| {
+        for title in [
+            "Party",
+            "2019 notional",
+            "Favourite gains",
+            "Favourite losses",
+            "Expected gains",
+            "Expected losses",
+            "Net change",
+        ] {
+            row.table_header(|header| {
+                header.text(title);
+                return header;
+            });
+        }
+        return row;
+    });
+
+    for change in changes {
+        let row = html::tables::TableRow::builder()
+            .table_cell(|data| {
+                data.push(make_party_swatch(&change.party));
+                data.text(change.party.to_string());
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(change.notional_seats.to_string());
                 return data;
             })
             .table_cell(|data| {
-                data.text(summary.median.to_string());
-                data.text(" [");
-                data.text(summary.lower_5th.to_string());
-                data.text(" - ");
-                data.text(summary.upper_95th.to_string());
-                data.text("]");
+                data.text(change.favourite_gains.to_string());
                 return data;
             })
             .table_cell(|data| {
-                data.text(format!("{:.2}%", summary.majority_percentage * 100.0));
+                data.text(change.favourite_losses.to_string());
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(format!(
+                    "{:.1} [{} - {}]",
+                    change.expected_gains, change.gains_lower_5th, change.gains_upper_95th
+                ));
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(format!(
+                    "{:.1} [{} - {}]",
+                    change.expected_losses, change.losses_lower_5th, change.losses_upper_95th
+                ));
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(format!(
+                    "{:+} [{:+} - {:+}]",
+                    change.net_change_median,
+                    change.net_change_lower_5th,
+                    change.net_change_upper_95th
+                ));
                 return data;
             })
             .build();
@@ -323,7 +1221,261 @@ fn make_summary_table(summaries: &Vec<MonteCarloSummarySimple>) -> html::tables:
     return table.build();
 }
 
-fn make_stats_table(stats: &Vec<(PartyName, i32)>) -> html::tables::Table {
+fn make_transitions_matrix(
+    changes: &[NotionalChange],
+    transitions: &[SeatTransition],
+) -> html::tables::Table {
+    // the rows are the parties defending seats, the columns those taking them
+    let losing_parties: Vec<&PartyName> = changes
+        .iter()
+        .filter(|change| change.notional_seats > 0)
+        .map(|change| &change.party)
+        .collect();
+    let gaining_parties: Vec<&PartyName> = changes
+        .iter()
+        .filter(|change| {
+            transitions
+                .iter()
+                .any(|transition| transition.to == change.party)
+        })
+        .map(|change| &change.party)
+        .collect();
+
+    let mut table = html::tables::Table::builder();
+    table.table_row(|row| {
+        row.table_header(|header| {
+            header.text("2019 ↓ / 2024 →");
+            return header;
+        });
+        for party in &gaining_parties {
+            row.table_header(|header| {
+                header.push(make_party_swatch(party));
+                header.text(party.abbreviation());
+                return header;
+            });
+        }
+        return row;
+    });
+
+    for from in &losing_parties {
+        let mut row = html::tables::TableRow::builder();
+        row.table_header(|header| {
+            header.push(make_party_swatch(from));
+            header.text(from.abbreviation());
+            return header;
+        });
+        for to in &gaining_parties {
+            let transition = transitions
+                .iter()
+                .find(|transition| transition.from == **from && transition.to == **to);
+            row.table_cell(|data| {
+                if let Some(transition) = transition {
+                    data.text(format!(
+                        "{:.1} ({})",
+                        transition.expected_seats, transition.favourite_seats
+                    ));
+                }
+                return data;
+            });
+        }
+        table.push(row.build());
+    }
+    return table.build();
+}
+
+fn make_seat_distribution_charts(
+    summaries: &[MonteCarloSummarySimple],
+) -> html::text_content::Division {
+    let mut division = html::text_content::Division::builder();
+    for summary in summaries.iter() {
+        let distribution = &summary.seat_distribution;
+        let max_count = distribution
+            .counts
+            .iter()
+            .max()
+            .cloned()
+            .unwrap_or(1)
+            .max(1);
+
+        let mut label = html::text_content::Paragraph::builder();
+        label.text(format!(
+            "{} {} ({} - {} seats)",
+            summary.party.to_string(),
+            summary.party.to_emoji(),
+            distribution.min_seats,
+            distribution.min_seats + distribution.counts.len() as i32 - 1
+        ));
+        division.push(label.build());
+
+        let mut chart = html::text_content::Division::builder();
+        chart.style(
+            "display: flex; align-items: flex-end; height: 60px; border-bottom: 1px solid #999;",
+        );
+        for (i, count) in distribution.counts.iter().enumerate() {
+            let seats = distribution.min_seats + i as i32;
+            let bar = html::text_content::Division::builder()
+                .style(format!(
+                    "flex: 1; background: {}; height: {:.1}%;",
+                    summary.party.colour(),
+                    *count as f64 / max_count as f64 * 100.0
+                ))
+                .title(format!("{} seats: {} simulations", seats, count))
+                .build();
+            chart.push(bar);
+        }
+        division.push(chart.build());
+
+        let percentiles = summary
+            .percentiles
+            .iter()
+            .map(|percentile| format!("{}%: {}", percentile.percentile, percentile.seats))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let mut caption = html::text_content::Paragraph::builder();
+        caption.style("font-size: small;");
+        caption.text(percentiles);
+        division.push(caption.build());
+    }
+    return division.build();
+}
+
+fn make_thin_market_reliance_table(reliance: &[ThinMarketReliance]) -> html::tables::Table {
+    let mut table = html::tables::Table::builder();
+    table.table_row(|row| {
+        row.table_header(|header| {
+            header.text("Party");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("Expected seats");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("From thin markets");
+            return header;
+        });
+        return row;
+    });
+
+    // leave out the parties with hardly any seats, their shares are just noise
+    for party in reliance.iter().filter(|party| party.expected_seats >= 0.5) {
+        let row = html::tables::TableRow::builder()
+            .table_cell(|data| {
+                data.push(make_party_swatch(&party.party));
+                data.text(party.party.to_string());
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(format!("{:.1}", party.expected_seats));
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(format!(
+                    "{:.1} ({:.1}%)",
+                    party.thin_expected_seats,
+                    party.thin_expected_seats / party.expected_seats * 100.0
+                ));
+                return data;
+            })
+            .build();
+        table.push(row);
+    }
+    return table.build();
+}
+
+fn make_scenarios_table(scenarios: &Scenarios) -> html::tables::Table {
+    let mut table = html::tables::Table::builder();
+    table.table_row(|row| {
+        row.table_header(|header| {
+            header.text("Scenario");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("Majority");
+            return header;
+        });
+        row.table_header(|header| {
+            header.text("Working majority");
+            return header;
+        });
+        return row;
+    });
+
+    let mut push_row = |scenario: String, majority: Option<f64>, working_majority: Option<f64>| {
+        let format_percentage = |percentage: Option<f64>| {
+            percentage
+                .map(|p| format!("{:.2}%", p * 100.0))
+                .unwrap_or("".to_string())
+        };
+        let row = html::tables::TableRow::builder()
+            .table_cell(|data| {
+                data.text(scenario);
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(format_percentage(majority));
+                return data;
+            })
+            .table_cell(|data| {
+                data.text(format_percentage(working_majority));
+                return data;
+            })
+            .build();
+        table.push(row);
+    };
+
+    push_row(
+        "Hung parliament".to_string(),
+        Some(scenarios.hung_parliament_percentage),
+        Some(scenarios.effective_hung_parliament_percentage),
+    );
+    for (party, percentage) in scenarios.effective_majority.iter() {
+        push_row(
+            format!("{} {} majority", party.to_string(), party.to_emoji()),
+            None,
+            Some(*percentage),
+        );
+    }
+    for bloc in scenarios.blocs.iter() {
+        let parties = bloc
+            .parties
+            .iter()
+            .map(|party| party.to_string())
+            .collect::<Vec<String>>()
+            .join(" + ");
+        let mut label = format!("{} majority", parties);
+        if !bloc.not_standing.is_empty() {
+            let not_standing = bloc
+                .not_standing
+                .iter()
+                .map(|party| party.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            label = format!("{} ({} not standing)", label, not_standing);
+        }
+        push_row(
+            label,
+            Some(bloc.majority_percentage),
+            Some(bloc.effective_majority_percentage),
+        );
+    }
+    for (party, percentage) in scenarios.largest_party.iter() {
+        push_row(
+            format!("{} {} largest party", party.to_string(), party.to_emoji()),
+            Some(*percentage),
+            None,
+        );
+    }
+    push_row(
+        "Tie for largest party".to_string(),
+        Some(scenarios.tied_largest_party_percentage),
+        None,
+    );
+
+    return table.build();
+}
+
+fn make_stats_table(stats: &[(PartyName, i32)]) -> html::tables::Table {
     let mut table = html::tables::Table::builder();
     table.table_row(|row| {
         row.table_header(|header| {
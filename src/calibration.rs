@@ -0,0 +1,258 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::coverage::identify_constituencies;
+use crate::normalisation::normalise_probabilities;
+use crate::{
+    CalibrationReport, DeclaredResult, NormalisationStrategy, PartyName, ReliabilityBin,
+    SeatIntervalCheck, Status,
+};
+
+/// Probabilities are clamped to at least this before taking logs, so a
+/// winner the market gave no chance doesn't make the log loss infinite.
+const MINIMUM_PROBABILITY: f64 = 1e-6;
+
+/// Score the markets in `status` against the actual results. Only seats
+/// with both a market and a result are scored, and each market's
+/// probabilities are normalised proportionally first.
+pub fn calibration_report(
+    status: &Status,
+    results: &[DeclaredResult],
+    number_of_bins: usize,
+) -> CalibrationReport {
+    let winners: HashMap<&str, &PartyName> = results
+        .iter()
+        .map(|result| (result.ons_code.as_str(), &result.winner))
+        .collect();
+    // older snapshots don't have ONS codes
+    let constituencies = identify_constituencies(&status.constituencies);
+
+    let mut seats_scored = 0;
+    let mut brier_total = 0.0;
+    let mut log_loss_total = 0.0;
+    // (predicted, won) for every party in every scored seat
+    let mut predictions: Vec<(f64, bool)> = Vec::new();
+    for constituency in &constituencies {
+        let winner = match winners.get(constituency.ons_code.as_str()) {
+            Some(winner) => *winner,
+            None => continue,
+        };
+        let parties =
+            normalise_probabilities(&constituency.parties, &NormalisationStrategy::Proportional);
+        seats_scored += 1;
+
+        let mut winner_probability = 0.0;
+        let mut winner_listed = false;
+        for party in &parties {
+            let won = party.name == *winner;
+            let outcome = if won { 1.0 } else { 0.0 };
+            brier_total += (party.probability - outcome).powi(2);
+            predictions.push((party.probability, won));
+            if won {
+                winner_probability += party.probability;
+                winner_listed = true;
+            }
+        }
+        // the winner wasn't even an answer, which the market said was impossible
+        if !winner_listed {
+            brier_total += 1.0;
+        }
+        log_loss_total -= winner_probability.max(MINIMUM_PROBABILITY).ln();
+    }
+
+    let seats = seats_scored.max(1) as f64;
+    return CalibrationReport {
+        fetched_at: status.fetched_at,
+        seats_scored,
+        brier_score: brier_total / seats,
+        log_loss: log_loss_total / seats,
+        reliability: reliability_bins(&predictions, number_of_bins),
+        seat_intervals: Vec::new(),
+    };
+}
+
+fn reliability_bins(predictions: &[(f64, bool)], number_of_bins: usize) -> Vec<ReliabilityBin> {
+    let number_of_bins = number_of_bins.max(1);
+    let mut bins: Vec<(usize, f64, usize)> = vec![(0, 0.0, 0); number_of_bins];
+    for (predicted, won) in predictions {
+        // a probability of exactly 1 goes in the top bin
+        let index = ((predicted * number_of_bins as f64) as usize).min(number_of_bins - 1);
+        let (count, predicted_total, wins) = &mut bins[index];
+        *count += 1;
+        *predicted_total += predicted;
+        if *won {
+            *wins += 1;
+        }
+    }
+    return bins
+        .into_iter()
+        .enumerate()
+        .map(|(i, (count, predicted_total, wins))| ReliabilityBin {
+            lower: i as f64 / number_of_bins as f64,
+            upper: (i + 1) as f64 / number_of_bins as f64,
+            count,
+            mean_predicted: predicted_total / count.max(1) as f64,
+            observed_frequency: wins as f64 / count.max(1) as f64,
+        })
+        .collect();
+}
+
+/// Check whether each party's actual seat total fell inside the simulated
+/// 5th to 95th percentile interval. `intervals` is `(party, median, lower_5th,
+/// upper_95th)`.
+pub fn seat_interval_checks(
+    intervals: &[(PartyName, i32, i32, i32)],
+    results: &[DeclaredResult],
+) -> Vec<SeatIntervalCheck> {
+    let mut actual_seats: BTreeMap<&PartyName, i32> = BTreeMap::new();
+    for result in results {
+        *actual_seats.entry(&result.winner).or_insert(0) += 1;
+    }
+    return intervals
+        .iter()
+        .map(|(party, median, lower_5th, upper_95th)| {
+            let actual = actual_seats.get(party).cloned().unwrap_or(0);
+            return SeatIntervalCheck {
+                party: party.clone(),
+                actual_seats: actual,
+                median: *median,
+                lower_5th: *lower_5th,
+                upper_95th: *upper_95th,
+                within_interval: *lower_5th <= actual && actual <= *upper_95th,
+            };
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::constituency_with_code;
+    use crate::ConstituencyStatus;
+    use chrono::Utc;
+
+    fn status(constituencies: Vec<ConstituencyStatus>) -> Status {
+        return Status {
+            fetched_at: Utc::now(),
+            constituencies,
+            market_errors: Vec::new(),
+            unparsed_answers: Vec::new(),
+        };
+    }
+
+    fn result(ons_code: &str, winner: PartyName) -> DeclaredResult {
+        return DeclaredResult {
+            ons_code: ons_code.to_string(),
+            constituency: ons_code.to_string(),
+            winner,
+        };
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn scores_brier_and_log_loss() {
+        let status = status(vec![
+            // normalised to 0.75 and 0.25
+            constituency_with_code(
+                "E1",
+                &[(PartyName::Labour, 0.9), (PartyName::Conservatives, 0.3)],
+            ),
+            // won by a party which wasn't an answer
+            constituency_with_code(
+                "E2",
+                &[(PartyName::Labour, 0.5), (PartyName::Conservatives, 0.5)],
+            ),
+            // no result yet, so not scored
+            constituency_with_code("E3", &[(PartyName::Labour, 1.0)]),
+        ]);
+        let results = vec![
+            result("E1", PartyName::Labour),
+            result("E2", PartyName::Reform),
+        ];
+        let report = calibration_report(&status, &results, 2);
+        assert_eq!(report.seats_scored, 2);
+        // (0.25² + 0.25²) and (0.5² + 0.5² + 1)
+        assert_close(report.brier_score, (0.125 + 1.5) / 2.0);
+        // the unlisted winner is clamped rather than infinite
+        assert_close(
+            report.log_loss,
+            (-(0.75_f64.ln()) - MINIMUM_PROBABILITY.ln()) / 2.0,
+        );
+    }
+
+    #[test]
+    fn clamps_the_log_loss_at_certainty() {
+        let status = status(vec![
+            constituency_with_code(
+                "E1",
+                &[(PartyName::Labour, 1.0), (PartyName::Conservatives, 0.0)],
+            ),
+            constituency_with_code(
+                "E2",
+                &[(PartyName::Labour, 1.0), (PartyName::Conservatives, 0.0)],
+            ),
+        ]);
+        let right = calibration_report(&status, &[result("E1", PartyName::Labour)], 10);
+        assert_close(right.brier_score, 0.0);
+        assert_close(right.log_loss, 0.0);
+        let wrong = calibration_report(&status, &[result("E2", PartyName::Conservatives)], 10);
+        assert_close(wrong.brier_score, 2.0);
+        assert_close(wrong.log_loss, -MINIMUM_PROBABILITY.ln());
+        assert!(wrong.log_loss.is_finite());
+    }
+
+    #[test]
+    fn bins_predictions_by_probability() {
+        let predictions = [(0.2, false), (0.8, true), (0.5, false), (1.0, true)];
+        let bins = reliability_bins(&predictions, 2);
+        assert_eq!(bins.len(), 2);
+        assert_close(bins[0].lower, 0.0);
+        assert_close(bins[0].upper, 0.5);
+        assert_eq!(bins[0].count, 1);
+        assert_close(bins[0].mean_predicted, 0.2);
+        assert_close(bins[0].observed_frequency, 0.0);
+        // 0.5 starts the top bin and 1 is in it too
+        assert_eq!(bins[1].count, 3);
+        assert_close(bins[1].mean_predicted, (0.8 + 0.5 + 1.0) / 3.0);
+        assert_close(bins[1].observed_frequency, 2.0 / 3.0);
+
+        let empty = reliability_bins(&[], 4);
+        assert!(empty
+            .iter()
+            .all(|bin| bin.count == 0 && bin.mean_predicted == 0.0));
+    }
+
+    #[test]
+    fn checks_seat_totals_against_the_intervals() {
+        let results = vec![
+            result("E1", PartyName::Labour),
+            result("E2", PartyName::Labour),
+            result("E3", PartyName::LiberalDemocrats),
+        ];
+        let intervals = vec![
+            (PartyName::Labour, 2, 1, 3),
+            (PartyName::LiberalDemocrats, 2, 2, 4),
+            (PartyName::Conservatives, 0, 0, 1),
+        ];
+        let checks = seat_interval_checks(&intervals, &results);
+        let summary: Vec<(&PartyName, i32, bool)> = checks
+            .iter()
+            .map(|check| (&check.party, check.actual_seats, check.within_interval))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (&PartyName::Labour, 2, true),
+                (&PartyName::LiberalDemocrats, 1, false),
+                (&PartyName::Conservatives, 0, true),
+            ]
+        );
+    }
+}
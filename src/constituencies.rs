@@ -0,0 +1,202 @@
+use std::sync::OnceLock;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{infer_nation, ConstituencyStatus, Nation};
+
+/// The 650 constituencies on the 2023 boundaries, with their ONS (GSS) codes.
+const CONSTITUENCIES_TSV: &str = include_str!("../data/constituencies.tsv");
+
+#[derive(Debug, Clone)]
+pub struct ConstituencyReference {
+    pub ons_code: String,
+    pub name: String,
+    pub nation: Nation,
+    /// The English region, or the nation's name outside England.
+    pub region: String,
+}
+
+pub fn reference_constituencies() -> &'static [ConstituencyReference] {
+    static CONSTITUENCIES: OnceLock<Vec<ConstituencyReference>> = OnceLock::new();
+    return CONSTITUENCIES.get_or_init(|| {
+        CONSTITUENCIES_TSV
+            .lines()
+            .skip(1)
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let columns: Vec<&str> = line.split('\t').collect();
+                return ConstituencyReference {
+                    ons_code: columns[0].to_string(),
+                    name: columns[1].to_string(),
+                    nation: match columns[2] {
+                        "England" => Nation::England,
+                        "Scotland" => Nation::Scotland,
+                        "Wales" => Nation::Wales,
+                        "NorthernIreland" => Nation::NorthernIreland,
+                        nation => panic!("Unknown nation in constituency table: {}", nation),
+                    },
+                    region: columns[3].to_string(),
+                };
+            })
+            .collect()
+    });
+}
+
+pub fn find_by_ons_code(ons_code: &str) -> Option<&'static ConstituencyReference> {
+    return reference_constituencies()
+        .iter()
+        .find(|constituency| constituency.ons_code == ons_code);
+}
+
+/// The nation from the reference table, or a guess from the parties standing
+/// if the constituency has no code.
+pub fn nation_of(constituency: &ConstituencyStatus) -> Nation {
+    return find_by_ons_code(&constituency.ons_code)
+        .map(|reference| reference.nation)
+        .unwrap_or_else(|| infer_nation(&constituency.parties));
+}
+
+/// The English region, or the nation outside England (and for constituencies
+/// without a code).
+pub fn region_of(constituency: &ConstituencyStatus) -> String {
+    return find_by_ons_code(&constituency.ons_code)
+        .map(|reference| reference.region.clone())
+        .unwrap_or_else(|| nation_of(constituency).to_string());
+}
+
+/// The areas a seat is broken down into: its region, and England as a whole
+/// for English seats.
+pub fn areas_of(constituency: &ConstituencyStatus) -> Vec<String> {
+    let mut areas = vec![region_of(constituency)];
+    // seats without a code have no region, so are only counted once
+    if nation_of(constituency) == Nation::England && areas[0] != Nation::England.to_string() {
+        areas.push(Nation::England.to_string());
+    }
+    return areas;
+}
+
+/// Lower-case, strip accents and punctuation and squash whitespace, so that
+/// e.g. `Ynys Môn` and `ynys mon ` compare equal. Accents are stripped by
+/// decomposing (NFKD) and dropping the combining marks.
+pub fn normalise_name(name: &str) -> String {
+    let mut normalised = String::new();
+    for c in name
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+    {
+        if c == '&' {
+            normalised.push_str(" and ");
+        } else if c.is_alphanumeric() {
+            normalised.push(c);
+        } else if c != '\'' {
+            normalised.push(' ');
+        }
+    }
+    return normalised
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+}
+
+/// Resolve a (possibly misspelt) constituency name from a market to the
+/// reference table. An exact match after normalising wins, otherwise the
+/// closest name by edit distance is used as long as it is close enough and
+/// there's only one such name.
+pub fn match_constituency(name: &str) -> Option<&'static ConstituencyReference> {
+    let normalised = normalise_name(name);
+    let constituencies = reference_constituencies();
+    if let Some(constituency) = constituencies
+        .iter()
+        .find(|constituency| normalise_name(&constituency.name) == normalised)
+    {
+        return Some(constituency);
+    }
+
+    let max_distance = (normalised.chars().count() / 10).max(2);
+    let mut distances: Vec<(usize, &ConstituencyReference)> = constituencies
+        .iter()
+        .map(|constituency| {
+            let distance = edit_distance(&normalised, &normalise_name(&constituency.name));
+            return (distance, constituency);
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    distances.sort_by_key(|(distance, _)| *distance);
+    return match distances.as_slice() {
+        [(_, best)] => Some(best),
+        [(best_distance, best), (next_distance, _), ..] if best_distance < next_distance => {
+            Some(best)
+        }
+        _ => None,
+    };
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let substitution = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + substitution);
+        }
+        previous = current;
+    }
+    return previous[b.len()];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ons_code(name: &str) -> Option<&'static str> {
+        return match_constituency(name).map(|constituency| constituency.ons_code.as_str());
+    }
+
+    #[test]
+    fn normalises_accents_and_punctuation() {
+        assert_eq!(normalise_name("Ynys Môn"), "ynys mon");
+        // decomposed, and an accent the table never listed
+        assert_eq!(normalise_name("Ynys Mo\u{302}n"), "ynys mon");
+        assert_eq!(normalise_name("YNYS MÕN "), "ynys mon");
+        assert_eq!(normalise_name("Weston-super-Mare"), "weston super mare");
+        assert_eq!(normalise_name("Bishop's Stortford"), "bishops stortford");
+        assert_eq!(
+            normalise_name("Dumfries & Galloway"),
+            "dumfries and galloway"
+        );
+    }
+
+    #[test]
+    fn matches_names_exactly_after_normalising() {
+        assert_eq!(ons_code("Ynys Mon"), Some("W07000112"));
+        assert_eq!(ons_code("ynys môn"), Some("W07000112"));
+        assert_eq!(ons_code("Weston super Mare"), Some("E14001581"));
+    }
+
+    #[test]
+    fn matches_misspellings_within_a_tenth_of_the_length() {
+        // 37 characters, so up to 3 edits
+        assert_eq!(
+            ons_code("Sheffield Brightsid and Hilsborogh"),
+            Some("E14001467")
+        );
+        assert_eq!(ons_code("Sheffield Brightsid and Hilsbrogh"), None);
+        // short names still allow 2 edits
+        assert_eq!(ons_code("Bristol Est"), Some("E14001132"));
+        assert_eq!(ons_code("Bristol Centre"), Some("E14001131"));
+        assert_eq!(ons_code("Bath Spa"), None);
+    }
+
+    #[test]
+    fn needs_a_unique_best_match() {
+        // one edit from both North East and North West
+        assert_eq!(ons_code("Bristol North Wast"), None);
+        // closer to North West than North East
+        assert_eq!(ons_code("Bristol Nrth West"), Some("E14001134"));
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::constituencies::{
+    find_by_ons_code, match_constituency, reference_constituencies, ConstituencyReference,
+};
+use crate::parties::party_info;
+use crate::{
+    ConstituencyStatus, CoverageReport, DuplicatedConstituency, ImputationStrategy, MarketMetadata,
+    MissingConstituency, Party, PartyName,
+};
+
+/// Older downloads don't have ONS codes, so look them up from the name.
+pub fn identify_constituencies(constituencies: &[ConstituencyStatus]) -> Vec<ConstituencyStatus> {
+    return constituencies
+        .iter()
+        .map(|constituency| {
+            if !constituency.ons_code.is_empty() {
+                return constituency.clone();
+            }
+            let ons_code = match_constituency(&constituency.constituency)
+                .map(|reference| reference.ons_code.clone())
+                .unwrap_or_default();
+            return ConstituencyStatus {
+                ons_code,
+                ..constituency.clone()
+            };
+        })
+        .collect();
+}
+
+/// Check the markets we have against the full list of constituencies.
+pub fn coverage_report(
+    constituencies: &[ConstituencyStatus],
+    imputation: &ImputationStrategy,
+) -> CoverageReport {
+    let mut markets_per_code: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut unidentified = Vec::new();
+    for constituency in constituencies {
+        if find_by_ons_code(&constituency.ons_code).is_some() {
+            *markets_per_code.entry(&constituency.ons_code).or_insert(0) += 1;
+        } else {
+            unidentified.push(constituency.constituency.clone());
+        }
+    }
+
+    let missing = reference_constituencies()
+        .iter()
+        .filter(|reference| !markets_per_code.contains_key(reference.ons_code.as_str()))
+        .map(|reference| MissingConstituency {
+            ons_code: reference.ons_code.clone(),
+            constituency: reference.name.clone(),
+        })
+        .collect();
+    let duplicated = markets_per_code
+        .iter()
+        .filter(|(_, markets)| **markets > 1)
+        .map(|(ons_code, markets)| DuplicatedConstituency {
+            ons_code: ons_code.to_string(),
+            constituency: find_by_ons_code(ons_code).unwrap().name.clone(),
+            markets: *markets,
+        })
+        .collect();
+
+    return CoverageReport {
+        expected_seats: reference_constituencies().len(),
+        // a seat with more than one market has none used
+        covered_seats: markets_per_code
+            .values()
+            .filter(|markets| **markets == 1)
+            .count(),
+        missing,
+        duplicated,
+        unidentified,
+        imputation: imputation.clone(),
+    };
+}
+
+/// Leave out every market for a constituency with more than one, as
+/// download_data does, since we can't tell which is right.
+pub fn remove_duplicates(constituencies: &[ConstituencyStatus]) -> Vec<ConstituencyStatus> {
+    let mut markets_per_code: HashMap<&str, usize> = HashMap::new();
+    for constituency in constituencies {
+        *markets_per_code.entry(&constituency.ons_code).or_insert(0) += 1;
+    }
+    return constituencies
+        .iter()
+        .filter(|constituency| {
+            constituency.ons_code.is_empty()
+                || markets_per_code[constituency.ons_code.as_str()] == 1
+        })
+        .cloned()
+        .collect();
+}
+
+/// Make up a market for every constituency without one, so that the seat
+/// totals are out of the full 650. The probabilities should already be
+/// normalised.
+pub fn impute_missing(
+    constituencies: &[ConstituencyStatus],
+    strategy: &ImputationStrategy,
+) -> Vec<ConstituencyStatus> {
+    if *strategy == ImputationStrategy::None {
+        return Vec::new();
+    }
+    let covered_codes: HashSet<&str> = constituencies
+        .iter()
+        .map(|constituency| constituency.ons_code.as_str())
+        .collect();
+    let known_constituencies: Vec<(&ConstituencyStatus, &ConstituencyReference)> = constituencies
+        .iter()
+        .filter_map(|constituency| {
+            find_by_ons_code(&constituency.ons_code).map(|reference| (constituency, reference))
+        })
+        .collect();
+
+    return reference_constituencies()
+        .iter()
+        .filter(|reference| !covered_codes.contains(reference.ons_code.as_str()))
+        .map(|reference| ConstituencyStatus {
+            ons_code: reference.ons_code.clone(),
+            constituency: reference.name.clone(),
+            parties: imputed_parties(constituencies, &known_constituencies, reference, strategy),
+            manifold_url: "".to_string(),
+            market: MarketMetadata::default(),
+        })
+        .collect();
+}
+
+fn imputed_parties(
+    constituencies: &[ConstituencyStatus],
+    known_constituencies: &[(&ConstituencyStatus, &ConstituencyReference)],
+    reference: &ConstituencyReference,
+    strategy: &ImputationStrategy,
+) -> Vec<Party> {
+    // use the most local markets available: the same region, the same nation,
+    // and failing that everywhere
+    let in_region: Vec<&ConstituencyStatus> = known_constituencies
+        .iter()
+        .filter(|(_, other)| other.region == reference.region)
+        .map(|(constituency, _)| *constituency)
+        .collect();
+    let in_nation: Vec<&ConstituencyStatus> = known_constituencies
+        .iter()
+        .filter(|(_, other)| other.nation == reference.nation)
+        .map(|(constituency, _)| *constituency)
+        .collect();
+    let neighbours = if !in_region.is_empty() {
+        in_region
+    } else if !in_nation.is_empty() {
+        in_nation
+    } else {
+        constituencies.iter().collect()
+    };
+
+    let mut totals: BTreeMap<PartyName, f64> = BTreeMap::new();
+    for constituency in &neighbours {
+        for party in &constituency.parties {
+            *totals.entry(party.name.clone()).or_insert(0.0) += party.probability;
+        }
+    }
+    // markets from elsewhere can have parties who don't stand in this nation
+    let standing: BTreeMap<PartyName, f64> = totals
+        .iter()
+        .filter(|(name, _)| party_info(name).nations.contains(&reference.nation))
+        .map(|(name, total)| (name.clone(), *total))
+        .collect();
+    if !standing.is_empty() {
+        totals = standing;
+    }
+    let total: f64 = totals.values().sum();
+
+    let mut parties: Vec<Party> = match strategy {
+        ImputationStrategy::None => Vec::new(),
+        ImputationStrategy::Uniform => totals
+            .keys()
+            .map(|name| Party {
+                name: name.clone(),
+                probability: 1.0 / totals.len() as f64,
+            })
+            .collect(),
+        ImputationStrategy::RegionalAverage => totals
+            .iter()
+            .map(|(name, party_total)| Party {
+                name: name.clone(),
+                probability: party_total / total,
+            })
+            .collect(),
+    };
+    // the same order as the markets, favourite first
+    parties.sort_by(|a, b| b.probability.partial_cmp(&a.probability).unwrap());
+    return parties;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::constituency_with_code as constituency;
+
+    fn probabilities(constituency: &ConstituencyStatus) -> BTreeMap<PartyName, f64> {
+        return constituency
+            .parties
+            .iter()
+            .map(|party| (party.name.clone(), party.probability))
+            .collect();
+    }
+
+    #[test]
+    fn drops_every_market_for_a_duplicated_seat() {
+        let constituencies = vec![
+            constituency("E14001080", &[(PartyName::Labour, 1.0)]),
+            constituency("E14001131", &[(PartyName::Labour, 1.0)]),
+            constituency("E14001080", &[(PartyName::Conservatives, 1.0)]),
+            constituency("", &[(PartyName::Labour, 1.0)]),
+            constituency("", &[(PartyName::Labour, 1.0)]),
+        ];
+        let unique = remove_duplicates(&constituencies);
+        let unique: Vec<&str> = unique
+            .iter()
+            .map(|constituency| constituency.ons_code.as_str())
+            .collect();
+        assert_eq!(unique, vec!["E14001131", "", ""]);
+
+        let report = coverage_report(&constituencies, &ImputationStrategy::None);
+        assert_eq!(report.covered_seats, 1);
+        assert_eq!(report.duplicated.len(), 1);
+        assert_eq!(report.duplicated[0].ons_code, "E14001080");
+        assert_eq!(report.duplicated[0].markets, 2);
+        assert_eq!(report.unidentified.len(), 2);
+        // the duplicated seat is listed as duplicated rather than missing
+        assert_eq!(report.missing.len(), 648);
+    }
+
+    #[test]
+    fn imputes_only_parties_standing_in_the_nation() {
+        let constituencies = vec![
+            // Bath
+            constituency(
+                "E14001080",
+                &[(PartyName::Labour, 0.5), (PartyName::Conservatives, 0.5)],
+            ),
+            // Bathgate and Linlithgow
+            constituency(
+                "S14000030",
+                &[(PartyName::SNP, 0.6), (PartyName::Labour, 0.4)],
+            ),
+        ];
+        let imputed = impute_missing(&constituencies, &ImputationStrategy::RegionalAverage);
+        assert_eq!(imputed.len(), 648);
+        let imputed_for = |ons_code: &str| {
+            return probabilities(
+                imputed
+                    .iter()
+                    .find(|constituency| constituency.ons_code == ons_code)
+                    .unwrap(),
+            );
+        };
+
+        // Bristol Central has Bath in the same region
+        assert_eq!(
+            imputed_for("E14001131"),
+            BTreeMap::from([(PartyName::Labour, 0.5), (PartyName::Conservatives, 0.5)])
+        );
+        // nothing in Wales, so everywhere, without the SNP
+        let ynys_mon = imputed_for("W07000112");
+        assert_eq!(ynys_mon.len(), 2);
+        assert!((ynys_mon[&PartyName::Labour] - 0.9 / 1.4).abs() < 1e-12);
+        assert!((ynys_mon[&PartyName::Conservatives] - 0.5 / 1.4).abs() < 1e-12);
+        // only the Conservatives of these stand in Northern Ireland
+        assert_eq!(
+            imputed_for("N05000001"),
+            BTreeMap::from([(PartyName::Conservatives, 1.0)])
+        );
+
+        let uniform = impute_missing(&constituencies, &ImputationStrategy::Uniform);
+        let ynys_mon = uniform
+            .iter()
+            .find(|constituency| constituency.ons_code == "W07000112")
+            .unwrap();
+        assert_eq!(
+            probabilities(ynys_mon),
+            BTreeMap::from([(PartyName::Labour, 0.5), (PartyName::Conservatives, 0.5)])
+        );
+        assert!(impute_missing(&constituencies, &ImputationStrategy::None).is_empty());
+    }
+}
@@ -0,0 +1,374 @@
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    AggregatedStats, ArchivedAggregate, ArchivedSummary, ChangeReport, ConstituencyStatus,
+    FlippedSeat, MarketChange, Party, PartyName, ProbabilityChange, SimulationChange, Status,
+};
+
+/// The parts of a `Status` or `AggregatedStats` that can be compared.
+pub struct Snapshot {
+    pub fetched_at: DateTime<Utc>,
+    pub constituencies: Vec<ConstituencyStatus>,
+    pub monte_carlo_summary: Option<Vec<ArchivedSummary>>,
+}
+
+impl Snapshot {
+    pub fn from_status(status: &Status) -> Snapshot {
+        return Snapshot {
+            fetched_at: status.fetched_at,
+            constituencies: status.constituencies.clone(),
+            monte_carlo_summary: None,
+        };
+    }
+
+    /// Imputed seats have no market, so they are left out.
+    pub fn from_aggregated(aggregated: &AggregatedStats) -> Snapshot {
+        return Snapshot {
+            fetched_at: aggregated.fetched_at,
+            constituencies: aggregated
+                .constituencies
+                .iter()
+                .filter(|constituency| !constituency.imputed)
+                .map(|constituency| ConstituencyStatus {
+                    ons_code: constituency.ons_code.clone(),
+                    constituency: constituency.constituency.clone(),
+                    parties: constituency.parties.clone(),
+                    manifold_url: constituency.manifold_url.clone(),
+                    market: constituency.market.clone(),
+                })
+                .collect(),
+            monte_carlo_summary: Some(
+                ArchivedAggregate::from_aggregated(aggregated).monte_carlo_summary,
+            ),
+        };
+    }
+
+    /// A run as it is archived: the markets as downloaded, and the
+    /// simulations from aggregating them.
+    pub fn from_archived(status: &Status, aggregate: &ArchivedAggregate) -> Snapshot {
+        return Snapshot {
+            monte_carlo_summary: Some(aggregate.monte_carlo_summary.clone()),
+            ..Snapshot::from_status(status)
+        };
+    }
+}
+
+/// Compare two runs, keeping the `number_of_changes` largest probability
+/// changes.
+pub fn change_report(
+    before: &Snapshot,
+    after: &Snapshot,
+    number_of_changes: usize,
+) -> ChangeReport {
+    let before_by_key = by_key(&before.constituencies);
+    let after_by_key = by_key(&after.constituencies);
+
+    let mut flipped_seats = Vec::new();
+    let mut probability_changes = Vec::new();
+    for (key, after_constituency) in &after_by_key {
+        let before_constituency = match before_by_key.get(key) {
+            Some(before_constituency) => before_constituency,
+            None => continue,
+        };
+        if let (Some(favourite_before), Some(favourite_after)) = (
+            favourite(&before_constituency.parties),
+            favourite(&after_constituency.parties),
+        ) {
+            if favourite_before.name != favourite_after.name {
+                flipped_seats.push(FlippedSeat {
+                    ons_code: after_constituency.ons_code.clone(),
+                    constituency: after_constituency.constituency.clone(),
+                    favourite_before: favourite_before.name.clone(),
+                    favourite_after: favourite_after.name.clone(),
+                    probability_before: favourite_before.probability,
+                    probability_after: favourite_after.probability,
+                });
+            }
+        }
+
+        // a party that appears in only one run counts as 0 in the other
+        let parties: BTreeSet<&PartyName> = before_constituency
+            .parties
+            .iter()
+            .chain(&after_constituency.parties)
+            .map(|party| &party.name)
+            .collect();
+        for party in parties {
+            probability_changes.push(ProbabilityChange {
+                ons_code: after_constituency.ons_code.clone(),
+                constituency: after_constituency.constituency.clone(),
+                party: party.clone(),
+                probability_before: probability_of(&before_constituency.parties, party),
+                probability_after: probability_of(&after_constituency.parties, party),
+            });
+        }
+    }
+    let change =
+        |change: &ProbabilityChange| (change.probability_after - change.probability_before).abs();
+    probability_changes.sort_by(|a, b| change(b).partial_cmp(&change(a)).unwrap());
+    probability_changes.truncate(number_of_changes);
+
+    let market_change = |constituency: &ConstituencyStatus| MarketChange {
+        ons_code: constituency.ons_code.clone(),
+        constituency: constituency.constituency.clone(),
+        manifold_url: constituency.manifold_url.clone(),
+    };
+    let added_markets = after_by_key
+        .iter()
+        .filter(|(key, _)| !before_by_key.contains_key(*key))
+        .map(|(_, constituency)| market_change(constituency))
+        .collect();
+    let removed_markets = before_by_key
+        .iter()
+        .filter(|(key, _)| !after_by_key.contains_key(*key))
+        .map(|(_, constituency)| market_change(constituency))
+        .collect();
+
+    let simulation_changes = match (&before.monte_carlo_summary, &after.monte_carlo_summary) {
+        (Some(before_summary), Some(after_summary)) => {
+            simulation_changes(before_summary, after_summary)
+        }
+        _ => Vec::new(),
+    };
+
+    return ChangeReport {
+        before_fetched_at: before.fetched_at,
+        after_fetched_at: after.fetched_at,
+        flipped_seats,
+        probability_changes,
+        simulation_changes,
+        added_markets,
+        removed_markets,
+    };
+}
+
+/// Constituencies are matched up by ONS code, or by name if they don't have one.
+fn by_key(constituencies: &[ConstituencyStatus]) -> BTreeMap<String, &ConstituencyStatus> {
+    return constituencies
+        .iter()
+        .map(|constituency| {
+            let key = if constituency.ons_code.is_empty() {
+                constituency.constituency.clone()
+            } else {
+                constituency.ons_code.clone()
+            };
+            return (key, constituency);
+        })
+        .collect();
+}
+
+fn favourite(parties: &[Party]) -> Option<&Party> {
+    return parties
+        .iter()
+        .max_by(|a, b| a.probability.partial_cmp(&b.probability).unwrap());
+}
+
+fn probability_of(parties: &[Party], name: &PartyName) -> f64 {
+    return parties
+        .iter()
+        .filter(|party| party.name == *name)
+        .map(|party| party.probability)
+        .sum();
+}
+
+fn simulation_changes(
+    before: &[ArchivedSummary],
+    after: &[ArchivedSummary],
+) -> Vec<SimulationChange> {
+    let find = |summaries: &[ArchivedSummary], party: &PartyName| {
+        summaries
+            .iter()
+            .find(|summary| summary.party == *party)
+            .map(|summary| (summary.median, summary.majority_percentage))
+    };
+    // in the order of the latest run, then any parties which dropped out
+    let mut parties: Vec<PartyName> = after.iter().map(|summary| summary.party.clone()).collect();
+    for summary in before {
+        if !parties.contains(&summary.party) {
+            parties.push(summary.party.clone());
+        }
+    }
+    return parties
+        .into_iter()
+        .map(|party| {
+            let before = find(before, &party);
+            let after = find(after, &party);
+            return SimulationChange {
+                party,
+                median_before: before.map(|(median, _)| median),
+                median_after: after.map(|(median, _)| median),
+                majority_percentage_before: before.map(|(_, majority)| majority),
+                majority_percentage_after: after.map(|(_, majority)| majority),
+            };
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::constituency_with_code;
+    use chrono::TimeZone;
+
+    fn snapshot(
+        hour: u32,
+        constituencies: Vec<ConstituencyStatus>,
+        summary: Option<Vec<ArchivedSummary>>,
+    ) -> Snapshot {
+        return Snapshot {
+            fetched_at: Utc.with_ymd_and_hms(2024, 6, 1, hour, 0, 0).unwrap(),
+            constituencies,
+            monte_carlo_summary: summary,
+        };
+    }
+
+    fn two_party(ons_code: &str, labour: f64) -> ConstituencyStatus {
+        return constituency_with_code(
+            ons_code,
+            &[
+                (PartyName::Labour, labour),
+                (PartyName::Conservatives, 1.0 - labour),
+            ],
+        );
+    }
+
+    fn summary(party: PartyName, median: i32, majority_percentage: f64) -> ArchivedSummary {
+        return ArchivedSummary {
+            party,
+            median,
+            lower_5th: median,
+            upper_95th: median,
+            majority_percentage,
+        };
+    }
+
+    #[test]
+    fn reports_flips_and_the_largest_changes() {
+        let before = snapshot(
+            1,
+            vec![
+                two_party("E1", 0.6),
+                two_party("E2", 0.7),
+                two_party("E3", 0.55),
+            ],
+            None,
+        );
+        let after = snapshot(
+            2,
+            vec![
+                two_party("E1", 0.4),
+                two_party("E2", 0.75),
+                // a new party in the seat counts as 0 before
+                constituency_with_code(
+                    "E3",
+                    &[
+                        (PartyName::Labour, 0.5),
+                        (PartyName::Conservatives, 0.2),
+                        (PartyName::Reform, 0.3),
+                    ],
+                ),
+            ],
+            None,
+        );
+        let report = change_report(&before, &after, 3);
+
+        assert_eq!(report.flipped_seats.len(), 1);
+        let flip = &report.flipped_seats[0];
+        assert_eq!(flip.ons_code, "E1");
+        assert_eq!(flip.favourite_before, PartyName::Labour);
+        assert_eq!(flip.favourite_after, PartyName::Conservatives);
+        assert!((flip.probability_before - 0.6).abs() < 1e-12);
+        assert!((flip.probability_after - 0.6).abs() < 1e-12);
+
+        let changes: Vec<(&str, &PartyName)> = report
+            .probability_changes
+            .iter()
+            .map(|change| (change.ons_code.as_str(), &change.party))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("E3", &PartyName::Reform),
+                ("E3", &PartyName::Conservatives),
+                ("E1", &PartyName::Conservatives),
+            ]
+        );
+        assert_eq!(report.probability_changes[0].probability_before, 0.0);
+        assert!(report.simulation_changes.is_empty());
+    }
+
+    #[test]
+    fn reports_added_and_removed_markets() {
+        let mut unnamed = two_party("", 0.5);
+        unnamed.constituency = "Somewhere".to_string();
+        let before = snapshot(1, vec![two_party("E1", 0.6), unnamed.clone()], None);
+        let after = snapshot(2, vec![unnamed, two_party("E2", 0.6)], None);
+        let report = change_report(&before, &after, 10);
+        let added: Vec<&str> = report
+            .added_markets
+            .iter()
+            .map(|market| market.ons_code.as_str())
+            .collect();
+        let removed: Vec<&str> = report
+            .removed_markets
+            .iter()
+            .map(|market| market.ons_code.as_str())
+            .collect();
+        assert_eq!(added, vec!["E2"]);
+        assert_eq!(removed, vec!["E1"]);
+        // matched by name without a code, and unchanged
+        assert!(report
+            .probability_changes
+            .iter()
+            .all(|change| change.probability_before == change.probability_after));
+    }
+
+    #[test]
+    fn compares_simulations_when_both_runs_have_them() {
+        let before = snapshot(
+            1,
+            Vec::new(),
+            Some(vec![
+                summary(PartyName::Conservatives, 300, 0.2),
+                summary(PartyName::Reform, 2, 0.0),
+            ]),
+        );
+        let after = snapshot(
+            2,
+            Vec::new(),
+            Some(vec![
+                summary(PartyName::Labour, 330, 0.6),
+                summary(PartyName::Conservatives, 250, 0.05),
+            ]),
+        );
+        let report = change_report(&before, &after, 10);
+        let parties: Vec<&PartyName> = report
+            .simulation_changes
+            .iter()
+            .map(|change| &change.party)
+            .collect();
+        // the latest run's order, then the party which dropped out
+        assert_eq!(
+            parties,
+            vec![
+                &PartyName::Labour,
+                &PartyName::Conservatives,
+                &PartyName::Reform
+            ]
+        );
+        let labour = &report.simulation_changes[0];
+        assert_eq!(labour.median_before, None);
+        assert_eq!(labour.median_after, Some(330));
+        let conservatives = &report.simulation_changes[1];
+        assert_eq!(conservatives.median_before, Some(300));
+        assert_eq!(conservatives.majority_percentage_after, Some(0.05));
+        let reform = &report.simulation_changes[2];
+        assert_eq!(reform.median_after, None);
+
+        let without = snapshot(3, Vec::new(), None);
+        assert!(change_report(&before, &without, 10)
+            .simulation_changes
+            .is_empty());
+    }
+}
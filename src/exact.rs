@@ -0,0 +1,263 @@
+use std::collections::BTreeSet;
+
+use crate::simulation::TOTAL_SEATS;
+use crate::{ConstituencyStatus, MonteCarloSummarySimple, PartyName, Percentile, SeatDistribution};
+
+/// Less probability than this in either tail is treated as impossible when
+/// taking quantiles.
+const NEGLIGIBLE_TAIL: f64 = 1e-9;
+
+/// The distribution of seats won after adding one more seat, won with
+/// `probability`.
+pub(crate) fn add_seat(pmf: &[f64], probability: f64) -> Vec<f64> {
+    let mut next = vec![0.0; pmf.len() + 1];
+    for (seats, p) in pmf.iter().enumerate() {
+        next[seats] += p * (1.0 - probability);
+        next[seats + 1] += p * probability;
+    }
+    return next;
+}
+
+/// The party's probability of winning each seat, as the simulation picks the
+/// winner. A party can be listed more than once in a seat, e.g. several
+/// independents, so its entries are summed, and a seat whose probabilities
+/// don't sum to 1 is scaled so that they do.
+pub(crate) fn party_probabilities(
+    constituencies: &[ConstituencyStatus],
+    name: &PartyName,
+) -> Vec<f64> {
+    return constituencies
+        .iter()
+        .map(|constituency| {
+            let total: f64 = constituency
+                .parties
+                .iter()
+                .map(|party| party.probability)
+                .sum();
+            if total <= 0.0 {
+                return 0.0;
+            }
+            let probability: f64 = constituency
+                .parties
+                .iter()
+                .filter(|party| party.name == *name)
+                .map(|party| party.probability)
+                .sum();
+            return probability / total;
+        })
+        .collect();
+}
+
+/// The exact probability of a party winning each number of seats
+/// (0..=TOTAL_SEATS), given its probability of winning each seat and that the
+/// seats are independent.
+#[derive(Debug, Clone)]
+pub struct ExactDistribution {
+    pub pmf: Vec<f64>,
+}
+
+impl ExactDistribution {
+    pub fn new(probabilities: &[f64]) -> ExactDistribution {
+        let mut pmf = vec![1.0];
+        for probability in probabilities {
+            pmf = add_seat(&pmf, *probability);
+        }
+        pmf.resize(TOTAL_SEATS.max(pmf.len() - 1) + 1, 0.0);
+        return ExactDistribution { pmf };
+    }
+
+    /// The smallest seat count with more than `q` of the probability at or
+    /// below it, the same as `SeatHistogram::quantile` with no sampling. As
+    /// there, `q = 0` and `q = 1` give the fewest and most seats that could
+    /// happen, ignoring tails too unlikely for any simulation to draw.
+    pub fn quantile(&self, q: f64) -> i32 {
+        let q = q.clamp(NEGLIGIBLE_TAIL, 1.0 - NEGLIGIBLE_TAIL);
+        let mut cumulative = 0.0;
+        for (seats, p) in self.pmf.iter().enumerate() {
+            cumulative += p;
+            if cumulative > q {
+                return seats as i32;
+            }
+        }
+        // rounding can leave the total just short of 1
+        let last = self.pmf.iter().rposition(|p| *p > 0.0).unwrap_or(0);
+        return last as i32;
+    }
+
+    pub fn median(&self) -> i32 {
+        return self.quantile(0.5);
+    }
+
+    /// The most likely seat count, ties resolve to the smallest.
+    pub fn mode(&self) -> i32 {
+        let mut mode = 0;
+        for (seats, p) in self.pmf.iter().enumerate() {
+            if *p > self.pmf[mode] {
+                mode = seats;
+            }
+        }
+        return mode as i32;
+    }
+
+    pub fn proportion_at_least(&self, seats: usize) -> f64 {
+        return self.pmf[seats.min(self.pmf.len())..].iter().sum();
+    }
+
+    /// As `SeatHistogram::to_distribution`, with the counts being the
+    /// expected number out of `number_of_simulations` so it can be drawn the
+    /// same way.
+    pub fn to_distribution(&self, number_of_simulations: usize) -> SeatDistribution {
+        let counts: Vec<u64> = self
+            .pmf
+            .iter()
+            .map(|p| (p * number_of_simulations as f64).round() as u64)
+            .collect();
+        let first = counts.iter().position(|count| *count > 0).unwrap_or(0);
+        let last = counts.iter().rposition(|count| *count > 0).unwrap_or(0);
+        return SeatDistribution {
+            min_seats: first as i32,
+            counts: counts[first..=last].to_vec(),
+        };
+    }
+}
+
+/// The same summaries `run_monte_carlo` gives for independent seats, but
+/// computed exactly. `majority_seats` is the line for `majority_percentage`.
+pub fn exact_summaries(
+    constituencies: &[ConstituencyStatus],
+    majority_seats: usize,
+    percentiles: &[f64],
+    number_of_simulations: usize,
+) -> Vec<MonteCarloSummarySimple> {
+    let parties: BTreeSet<&PartyName> = constituencies
+        .iter()
+        .flat_map(|constituency| constituency.parties.iter().map(|party| &party.name))
+        .collect();
+    let mut summaries: Vec<MonteCarloSummarySimple> = parties
+        .into_iter()
+        .map(|name| {
+            let probabilities = party_probabilities(constituencies, name);
+            return (name, ExactDistribution::new(&probabilities));
+        })
+        // only include parties with some chance of a seat
+        .filter(|(_, distribution)| distribution.pmf[0] < 1.0)
+        .map(|(name, distribution)| MonteCarloSummarySimple {
+            party: name.clone(),
+            mode: distribution.mode(),
+            median: distribution.median(),
+            lower_5th: distribution.quantile(0.05),
+            upper_95th: distribution.quantile(0.95),
+            majority_percentage: distribution.proportion_at_least(majority_seats),
+            percentiles: percentiles
+                .iter()
+                .map(|percentile| Percentile {
+                    percentile: *percentile,
+                    seats: distribution.quantile(percentile / 100.0),
+                })
+                .collect(),
+            seat_distribution: distribution.to_distribution(number_of_simulations),
+        })
+        .collect();
+
+    // sort by the median
+    summaries.sort_by_key(|summary| std::cmp::Reverse(summary.median));
+
+    return summaries;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::constituency;
+    use crate::simulation::{run_monte_carlo, SimulationConfig, MAJORITY_SEATS};
+    use crate::SimulationMode;
+
+    /// The exact summaries should agree with the independent simulation up to
+    /// sampling noise.
+    fn assert_matches_monte_carlo(constituencies: &[ConstituencyStatus]) {
+        let config = SimulationConfig {
+            mode: SimulationMode::Independent,
+            number_of_simulations: 5000,
+            seed: 1,
+            blocs: Vec::new(),
+        };
+        let simulated = run_monte_carlo(constituencies, &config).summaries();
+        let exact = exact_summaries(constituencies, MAJORITY_SEATS, &[], 5000);
+        assert_eq!(simulated.len(), exact.len());
+        for summary in &simulated {
+            let exact_summary = exact
+                .iter()
+                .find(|exact_summary| exact_summary.party == summary.party)
+                .unwrap();
+            assert!((summary.median - exact_summary.median).abs() <= 1);
+            assert!((summary.lower_5th - exact_summary.lower_5th).abs() <= 2);
+            assert!((summary.upper_95th - exact_summary.upper_95th).abs() <= 2);
+            assert!((summary.majority_percentage - exact_summary.majority_percentage).abs() < 0.03);
+        }
+    }
+
+    #[test]
+    fn quantiles_at_the_edges() {
+        // always wins one seat, and the other half the time
+        let distribution = ExactDistribution::new(&[1.0, 0.5, 0.0]);
+        assert_eq!(distribution.quantile(0.0), 1);
+        assert_eq!(distribution.quantile(0.5), 2);
+        assert_eq!(distribution.quantile(1.0), 2);
+        // a seat with a tiny chance still isn't the most it could win
+        let distribution = ExactDistribution::new(&[1.0, 1e-12]);
+        assert_eq!(distribution.quantile(1.0), 1);
+    }
+
+    #[test]
+    fn matches_monte_carlo() {
+        let constituencies: Vec<ConstituencyStatus> = (0..650)
+            .map(|seat| {
+                let labour = 0.3 + 0.4 * (seat % 7) as f64 / 6.0;
+                return constituency(&[
+                    (PartyName::Labour, labour),
+                    (PartyName::Conservatives, (1.0 - labour) * 0.7),
+                    (PartyName::LiberalDemocrats, (1.0 - labour) * 0.3),
+                ]);
+            })
+            .collect();
+        assert_matches_monte_carlo(&constituencies);
+    }
+
+    #[test]
+    fn matches_monte_carlo_with_repeated_parties() {
+        let constituencies: Vec<ConstituencyStatus> = (0..650)
+            .map(|_| {
+                constituency(&[
+                    (PartyName::Labour, 0.5),
+                    (PartyName::Independent, 0.25),
+                    (PartyName::Independent, 0.25),
+                ])
+            })
+            .collect();
+        assert_matches_monte_carlo(&constituencies);
+        let exact = exact_summaries(&constituencies, MAJORITY_SEATS, &[], 5000);
+        let independent = exact
+            .iter()
+            .find(|summary| summary.party == PartyName::Independent)
+            .unwrap();
+        assert_eq!(independent.median, 325);
+    }
+
+    #[test]
+    fn matches_monte_carlo_with_unnormalised_seats() {
+        // a 20% overround in every seat, as with no normalisation
+        let constituencies: Vec<ConstituencyStatus> = (0..650)
+            .map(|seat| {
+                let labour = 0.4 + 0.4 * (seat % 3) as f64 / 2.0;
+                return constituency(&[
+                    (PartyName::Labour, labour),
+                    (PartyName::Conservatives, 1.2 - labour),
+                ]);
+            })
+            .collect();
+        let labour = party_probabilities(&constituencies, &PartyName::Labour);
+        assert!((labour[0] - 0.4 / 1.2).abs() < 1e-12);
+        assert!((labour[2] - 0.8 / 1.2).abs() < 1e-12);
+        assert_matches_monte_carlo(&constituencies);
+    }
+}
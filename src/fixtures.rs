@@ -0,0 +1,28 @@
+//! Shared builders for the unit tests.
+
+use crate::{ConstituencyStatus, MarketMetadata, Party, PartyName};
+
+/// A seat with the given ONS code, named after it.
+pub(crate) fn constituency_with_code(
+    ons_code: &str,
+    parties: &[(PartyName, f64)],
+) -> ConstituencyStatus {
+    return ConstituencyStatus {
+        ons_code: ons_code.to_string(),
+        constituency: ons_code.to_string(),
+        parties: parties
+            .iter()
+            .map(|(name, probability)| Party {
+                name: name.clone(),
+                probability: *probability,
+            })
+            .collect(),
+        manifold_url: "".to_string(),
+        market: MarketMetadata::default(),
+    };
+}
+
+/// A seat without a code, as in the simulation tests.
+pub(crate) fn constituency(parties: &[(PartyName, f64)]) -> ConstituencyStatus {
+    return constituency_with_code("", parties);
+}
@@ -1,17 +1,111 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+pub mod archive;
+pub mod calibration;
+pub mod constituencies;
+pub mod coverage;
+pub mod diff;
+pub mod exact;
+#[cfg(test)]
+mod fixtures;
+pub mod manifold;
+pub mod normalisation;
+pub mod notional;
+pub mod parties;
+pub mod quality;
+pub mod results;
+pub mod sensitivity;
+pub mod simulation;
+pub mod time_series;
+pub mod trajectories;
+
+use simulation::SeatHistogram;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Status {
     pub fetched_at: DateTime<Utc>,
     pub constituencies: Vec<ConstituencyStatus>,
+    /// Markets which were left out of `constituencies`, and why.
+    #[serde(default)]
+    pub market_errors: Vec<MarketError>,
+    /// Answers which didn't match any party alias.
+    #[serde(default)]
+    pub unparsed_answers: Vec<UnparsedAnswer>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnparsedAnswer {
+    pub text: String,
+    pub markets: Vec<UnparsedAnswerMarket>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnparsedAnswerMarket {
+    pub market_id: String,
+    pub question: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MarketError {
+    pub market_id: String,
+    pub question: String,
+    pub kind: MarketErrorKind,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum MarketErrorKind {
+    FetchFailed {
+        message: String,
+    },
+    UnmatchedConstituency {
+        name: String,
+    },
+    /// More than one market resolved to this constituency, so none are used.
+    DuplicateConstituency {
+        ons_code: String,
+    },
+}
+
+impl MarketErrorKind {
+    pub fn to_string(&self) -> String {
+        match self {
+            MarketErrorKind::FetchFailed { message } => format!("could not fetch: {}", message),
+            MarketErrorKind::UnmatchedConstituency { name } => {
+                format!("no constituency matches `{}`", name)
+            }
+            MarketErrorKind::DuplicateConstituency { ons_code } => {
+                format!("another market is also for {}", ons_code)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ConstituencyStatus {
+    /// The GSS code, e.g. `E14001063`.
+    #[serde(default)]
+    pub ons_code: String,
     pub constituency: String,
     pub parties: Vec<Party>,
     pub manifold_url: String,
+    #[serde(default)]
+    pub market: MarketMetadata,
+}
+
+/// How much trading a market has seen, to judge how far to trust its prices.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MarketMetadata {
+    /// Total mana traded.
+    pub volume: f64,
+    pub total_liquidity: f64,
+    pub unique_bettors: u32,
+    pub created_time: Option<DateTime<Utc>>,
+    pub close_time: Option<DateTime<Utc>>,
+    pub last_bet_time: Option<DateTime<Utc>>,
+    pub is_resolved: bool,
+    /// The winning answer's id, or e.g. `CANCEL`, once resolved.
+    pub resolution: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -36,51 +130,96 @@ pub enum PartyName {
     WorkersPartyOfBritain,
     Reform,
     Other,
+    /// Stands for re-election unopposed by the main parties.
+    Speaker,
+    /// A party named only in `data/parties.json` or the alias config, which
+    /// doesn't have a variant of its own yet.
+    Registered(String),
     Unparsed(String),
 }
 
 impl PartyName {
+    pub fn to_string(&self) -> String {
+        return parties::party_info(self).display_name;
+    }
+
+    pub fn to_emoji(&self) -> String {
+        return parties::party_info(self).emoji;
+    }
+
+    pub fn abbreviation(&self) -> String {
+        return parties::party_info(self).abbreviation;
+    }
+
+    pub fn colour(&self) -> String {
+        return parties::party_info(self).colour;
+    }
+}
+
+/// Everything about a party that isn't its name, from `data/parties.json`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PartyInfo {
+    #[serde(deserialize_with = "parties::deserialize_party_key")]
+    pub party: PartyName,
+    pub display_name: String,
+    pub abbreviation: String,
+    /// A hex colour for charts, e.g. `#E4003B`.
+    pub colour: String,
+    pub emoji: String,
+    /// The nations the party stands candidates in.
+    pub nations: Vec<Nation>,
+}
+
+#[derive(Eq, Ord, PartialOrd, Serialize, Deserialize, PartialEq, Hash, Clone, Copy, Debug)]
+pub enum Nation {
+    England,
+    Scotland,
+    Wales,
+    NorthernIreland,
+}
+
+impl Nation {
     pub fn to_string(&self) -> String {
         match self {
-            PartyName::Conservatives => "Conservatives".to_string(),
-            PartyName::Labour => "Labour".to_string(),
-            PartyName::LiberalDemocrats => "Liberal Democrats".to_string(),
-            PartyName::SNP => "Scottish National Party".to_string(),
-            PartyName::Green => "Green".to_string(),
-            PartyName::PlaidCymru => "Plaid Cymru".to_string(),
-            PartyName::DUP => "Democratic Unionist Party".to_string(),
-            PartyName::SinnFein => "Sinn Féin".to_string(),
-            PartyName::SDLP => "Social Democratic and Labour Party".to_string(),
-            PartyName::Alliance => "Alliance".to_string(),
-            PartyName::Independent => "Independent".to_string(),
-            PartyName::WorkersPartyOfBritain => "Workers Party of Britain".to_string(),
-            PartyName::Reform => "Reform".to_string(),
-            PartyName::Other => "Other".to_string(),
-            PartyName::Unparsed(name) => name.to_string(),
+            Nation::England => "England".to_string(),
+            Nation::Scotland => "Scotland".to_string(),
+            Nation::Wales => "Wales".to_string(),
+            Nation::NorthernIreland => "Northern Ireland".to_string(),
         }
     }
 }
 
-impl PartyName {
-    pub fn to_emoji(&self) -> String {
-        match self {
-            PartyName::Conservatives => "🌳".to_string(),
-            PartyName::Labour => "🌹".to_string(),
-            PartyName::LiberalDemocrats => "🕊️".to_string(),
-            PartyName::SNP => "🎗️".to_string(),
-            PartyName::Green => "🌱".to_string(),
-            PartyName::PlaidCymru => "🌼".to_string(),
-            PartyName::DUP => "🦁".to_string(),
-            PartyName::SinnFein => "🇮🇪".to_string(),
-            PartyName::SDLP => "".to_string(),
-            PartyName::Alliance => "".to_string(),
-            PartyName::Independent => "".to_string(),
-            PartyName::WorkersPartyOfBritain => "⚙️".to_string(),
-            PartyName::Reform => "".to_string(),
-            PartyName::Other => "".to_string(),
-            PartyName::Unparsed(_) => "".to_string(),
+/// Guess which nation a constituency is in from the parties standing there,
+/// using the parties which only stand in one nation.
+pub fn infer_nation(parties: &[Party]) -> Nation {
+    for nation in [Nation::NorthernIreland, Nation::Scotland, Nation::Wales] {
+        let has_nationalist_party = parties
+            .iter()
+            .any(|party| parties::party_info(&party.name).nations == [nation]);
+        if has_nationalist_party {
+            return nation;
+        }
+    }
+    return Nation::England;
+}
+
+///////// Command line options
+
+/// Look up `--name value` in the command line arguments, falling back to the
+/// `ELECTION_NAME` environment variable (upper-cased, dashes as underscores).
+pub fn get_option(name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let args: Vec<String> = std::env::args().collect();
+    for (i, arg) in args.iter().enumerate() {
+        if *arg == flag {
+            return args.get(i + 1).cloned();
+        }
+        if let Some(value) = arg.strip_prefix(&format!("{}=", flag)) {
+            return Some(value.to_string());
         }
     }
+    let env_var = format!("ELECTION_{}", name.to_uppercase().replace('-', "_"));
+    return std::env::var(env_var).ok();
 }
 
 ///////// Aggregate Stats
@@ -88,9 +227,245 @@ impl PartyName {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AggregatedStats {
     pub fetched_at: DateTime<Utc>,
+    /// The registry entries for every party in `constituencies`.
+    pub parties: Vec<PartyInfo>,
     pub constituencies: Vec<ConstituencyAggregated>,
     pub winning_constituencies: Vec<(PartyName, i32)>,
+    /// The seed used for the simulations, pass it back in to reproduce the run.
+    pub seed: u64,
+    pub number_of_simulations: usize,
+    pub simulation_mode: SimulationMode,
     pub monte_carlo_summary: Vec<MonteCarloSummarySimple>,
+    /// The independent-seats simulation, kept for comparison when another mode is used.
+    pub baseline_monte_carlo_summary: Option<Vec<MonteCarloSummarySimple>>,
+    pub scenarios: Scenarios,
+    pub normalisation: NormalisationStrategy,
+    pub overround_threshold: f64,
+    pub coverage: CoverageReport,
+    pub quality_threshold: f64,
+    pub thin_market_adjustment: ThinMarketAdjustment,
+    /// How many of each party's expected seats come from thin markets, most
+    /// reliant first.
+    pub thin_market_reliance: Vec<ThinMarketReliance>,
+    pub results: ResultsSummary,
+    /// The same as above for each English region, each nation and England as
+    /// a whole.
+    pub regions: Vec<RegionBreakdown>,
+    pub notional_2019: NotionalComparison,
+    /// The seats which most often decide a majority, most pivotal first.
+    pub pivotal_seats: Vec<PivotalSeat>,
+    pub sensitivity_shift: f64,
+    /// Every party's most influential seats, ranked by the effect on its
+    /// majority probability or, when that can't move, its expected seats.
+    pub sensitivity: Vec<PartySensitivity>,
+}
+
+/// What happens when one party's probability in one seat goes up by the
+/// sensitivity shift (up to 1), taken from the other parties there in
+/// proportion. Exact when the seats are independent.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SeatSensitivity {
+    pub ons_code: String,
+    pub constituency: String,
+    pub party: PartyName,
+    /// Before the shift.
+    pub probability: f64,
+    /// For every party standing in the seat, `party` first.
+    pub effects: Vec<PartyEffect>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PartyEffect {
+    pub party: PartyName,
+    pub expected_seats_change: f64,
+    pub majority_percentage_change: f64,
+}
+
+/// The seats where a shift towards `party` moves its majority chance most.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PartySensitivity {
+    pub party: PartyName,
+    pub seats: Vec<SeatSensitivity>,
+}
+
+/// A seat which takes a party to a majority, ordering the party's seats from
+/// its most to least likely win.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PivotalSeat {
+    pub ons_code: String,
+    pub constituency: String,
+    /// The party the seat most often tips over the line.
+    pub party: PartyName,
+    /// How often the seat was the one taking `party` to 326.
+    pub tipping_point_probability: f64,
+    /// The correlation between `party` winning the seat and winning a majority.
+    pub majority_correlation: f64,
+}
+
+/// What would change from the notional 2019 result, i.e. the 2019 election
+/// replayed on the new boundaries. Only seats with a notional winner count.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NotionalComparison {
+    pub seats_with_notional: usize,
+    /// Biggest net change first.
+    pub changes: Vec<NotionalChange>,
+    /// Which party takes seats from which, most seats first.
+    pub transitions: Vec<SeatTransition>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NotionalChange {
+    pub party: PartyName,
+    pub notional_seats: i32,
+    /// If every favourite won.
+    pub favourite_gains: i32,
+    pub favourite_losses: i32,
+    /// Across the simulations.
+    pub expected_gains: f64,
+    pub gains_lower_5th: i32,
+    pub gains_upper_95th: i32,
+    pub expected_losses: f64,
+    pub losses_lower_5th: i32,
+    pub losses_upper_95th: i32,
+    pub net_change_median: i32,
+    pub net_change_lower_5th: i32,
+    pub net_change_upper_95th: i32,
+}
+
+/// Seats moving from their notional 2019 winner to another party.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SeatTransition {
+    pub from: PartyName,
+    pub to: PartyName,
+    /// If every favourite won.
+    pub favourite_seats: i32,
+    /// The mean across the simulations.
+    pub expected_seats: f64,
+}
+
+/// The favourites and simulated seats within one area, from the same
+/// simulations as the national totals.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RegionBreakdown {
+    pub region: String,
+    pub seats: usize,
+    pub winning_constituencies: Vec<(PartyName, i32)>,
+    /// `majority_percentage` here is how often a party won more than half of
+    /// the area's seats.
+    pub monte_carlo_summary: Vec<MonteCarloSummarySimple>,
+}
+
+/// A seat whose winner has been declared, from the results file.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeclaredResult {
+    pub ons_code: String,
+    pub constituency: String,
+    pub winner: PartyName,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ResultsSummary {
+    pub declared_seats: usize,
+    pub undeclared_seats: usize,
+    /// Seats won so far, most first.
+    pub declared_by_party: Vec<(PartyName, i32)>,
+}
+
+/// What to do with thinly traded markets before simulating.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum ThinMarketAdjustment {
+    /// Use their prices as they are.
+    None,
+    /// Shrink them toward the average of the markets in the same region.
+    ShrinkToRegion,
+    /// Shrink them toward the average of the markets in the same nation.
+    ShrinkToNation,
+}
+
+impl ThinMarketAdjustment {
+    pub fn to_string(&self) -> String {
+        match self {
+            ThinMarketAdjustment::None => "None".to_string(),
+            ThinMarketAdjustment::ShrinkToRegion => "Shrink to region".to_string(),
+            ThinMarketAdjustment::ShrinkToNation => "Shrink to nation".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ThinMarketReliance {
+    pub party: PartyName,
+    /// The sum of the party's probabilities over every seat.
+    pub expected_seats: f64,
+    /// The same, but only over seats with a thin (or no) market.
+    pub thin_expected_seats: f64,
+}
+
+/// How to fill in constituencies which don't have a market.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum ImputationStrategy {
+    /// Leave them out, so they count for nobody.
+    None,
+    /// Every party standing in nearby seats is equally likely.
+    Uniform,
+    /// The average of the markets in the same region.
+    RegionalAverage,
+}
+
+impl ImputationStrategy {
+    pub fn to_string(&self) -> String {
+        match self {
+            ImputationStrategy::None => "None".to_string(),
+            ImputationStrategy::Uniform => "Uniform".to_string(),
+            ImputationStrategy::RegionalAverage => "Regional average".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CoverageReport {
+    pub expected_seats: usize,
+    pub covered_seats: usize,
+    pub missing: Vec<MissingConstituency>,
+    pub duplicated: Vec<DuplicatedConstituency>,
+    /// Markets without an ONS code, which can't be checked.
+    pub unidentified: Vec<String>,
+    pub imputation: ImputationStrategy,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MissingConstituency {
+    pub ons_code: String,
+    pub constituency: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicatedConstituency {
+    pub ons_code: String,
+    pub constituency: String,
+    pub markets: usize,
+}
+
+/// How a market's probabilities are rescaled to sum to 1 before they're used.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum NormalisationStrategy {
+    /// Leave the probabilities as they are on Manifold.
+    None,
+    /// Divide every probability by the market's total.
+    Proportional,
+    /// Raise every probability to a common power, correcting for the
+    /// favourite-longshot bias in the same spirit as Shin's method.
+    Power,
+}
+
+impl NormalisationStrategy {
+    pub fn to_string(&self) -> String {
+        match self {
+            NormalisationStrategy::None => "None".to_string(),
+            NormalisationStrategy::Proportional => "Proportional".to_string(),
+            NormalisationStrategy::Power => "Power".to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -105,11 +480,57 @@ pub struct ConstituencyStats {
     pub third_place_probability: Option<f64>,
 }
 
-pub const NUMBER_OF_SIMULATIONS: usize = 100_000;
+pub const DEFAULT_NUMBER_OF_SIMULATIONS: usize = 100_000;
+
+pub const DEFAULT_PERCENTILES: [f64; 6] = [1.0, 10.0, 25.0, 75.0, 90.0, 99.0];
+
+/// Party names separated by `+`, blocs separated by `,`.
+pub const DEFAULT_BLOCS: &str =
+    "Labour+LiberalDemocrats,Conservatives+Reform,Labour+LiberalDemocrats+SNP";
+
+/// Markets whose probabilities sum to further than this from 1 are flagged.
+pub const DEFAULT_OVERROUND_THRESHOLD: f64 = 0.05;
+
+/// Markets scoring below this are flagged as thin.
+pub const DEFAULT_QUALITY_THRESHOLD: f64 = 0.3;
+
+pub const DEFAULT_NATIONAL_SHOCK: f64 = 0.4;
+pub const DEFAULT_NATION_SHOCK: f64 = 0.2;
+
+/// How far a market is moved for the sensitivity analysis, i.e. 10 points.
+pub const DEFAULT_SENSITIVITY_SHIFT: f64 = 0.1;
+/// How many of the most influential seats are kept for each party.
+pub const DEFAULT_SENSITIVE_SEATS: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum SimulationMode {
+    /// Every constituency is drawn independently from its market probabilities.
+    Independent,
+    /// Each simulation draws a shared log-odds shock per party (with the given
+    /// standard deviation), plus a per-nation shock, and applies them to every
+    /// seat's probabilities before sampling.
+    Correlated {
+        national_shock: f64,
+        nation_shock: f64,
+    },
+    /// As `Independent`, but the seat totals are worked out exactly rather
+    /// than sampled. The scenarios still come from independent simulations.
+    Exact,
+}
+
+impl SimulationMode {
+    pub fn to_string(&self) -> String {
+        match self {
+            SimulationMode::Independent => "Independent".to_string(),
+            SimulationMode::Correlated { .. } => "Correlated".to_string(),
+            SimulationMode::Exact => "Exact".to_string(),
+        }
+    }
+}
 
 pub struct MonteCarloSummary {
     pub party: PartyName,
-    pub seats: Vec<i32>,
+    pub seats: SeatHistogram,
     pub mode: i32,
     pub median: i32,
     pub lower_5th: i32,
@@ -125,9 +546,28 @@ pub struct MonteCarloSummarySimple {
     pub lower_5th: i32,
     pub upper_95th: i32,
     pub majority_percentage: f64,
+    pub percentiles: Vec<Percentile>,
+    pub seat_distribution: SeatDistribution,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Percentile {
+    pub percentile: f64,
+    pub seats: i32,
+}
+
+/// The number of simulations giving each seat count from `min_seats` upwards,
+/// trimmed of the empty counts at either end.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SeatDistribution {
+    pub min_seats: i32,
+    pub counts: Vec<u64>,
 }
 
-pub fn to_simple_summary(summary: &MonteCarloSummary) -> MonteCarloSummarySimple {
+pub fn to_simple_summary(
+    summary: &MonteCarloSummary,
+    percentiles: &[f64],
+) -> MonteCarloSummarySimple {
     return MonteCarloSummarySimple {
         party: summary.party.clone(),
         mode: summary.mode,
@@ -135,13 +575,294 @@ pub fn to_simple_summary(summary: &MonteCarloSummary) -> MonteCarloSummarySimple
         lower_5th: summary.lower_5th,
         upper_95th: summary.upper_95th,
         majority_percentage: summary.majority_percentage,
+        percentiles: percentiles
+            .iter()
+            .map(|percentile| Percentile {
+                percentile: *percentile,
+                seats: summary.seats.quantile(percentile / 100.0),
+            })
+            .collect(),
+        seat_distribution: summary.seats.to_distribution(),
     };
 }
 
+/// The share of simulated parliaments falling into each scenario. An effective
+/// majority ignores the Speaker and Sinn Féin's abstentionist seats.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Scenarios {
+    pub hung_parliament_percentage: f64,
+    pub effective_hung_parliament_percentage: f64,
+    pub largest_party: Vec<(PartyName, f64)>,
+    pub tied_largest_party_percentage: f64,
+    pub effective_majority: Vec<(PartyName, f64)>,
+    pub blocs: Vec<BlocScenario>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlocScenario {
+    /// The bloc as configured.
+    #[serde(default)]
+    pub name: String,
+    pub parties: Vec<PartyName>,
+    /// Members of the bloc who aren't standing anywhere, so add no seats.
+    #[serde(default)]
+    pub not_standing: Vec<PartyName>,
+    pub majority_percentage: f64,
+    pub effective_majority_percentage: f64,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ConstituencyAggregated {
+    pub ons_code: String,
     pub constituency: String,
     pub parties: Vec<Party>,
     pub manifold_url: String,
+    pub nation: Nation,
+    /// The English region, or the nation outside England.
+    pub region: String,
+    pub notional_2019_winner: Option<PartyName>,
+    /// The sum of the market's probabilities before normalisation.
+    pub overround: f64,
+    pub overround_flagged: bool,
+    /// There was no market, so the probabilities come from the imputation prior.
+    pub imputed: bool,
+    #[serde(default)]
+    pub market: MarketMetadata,
+    /// From 0 to 1, see `quality::market_quality`.
+    pub market_quality: f64,
+    pub thin_market: bool,
+    /// The actual winner, once the seat has declared.
+    pub declared_winner: Option<PartyName>,
     pub stats: ConstituencyStats,
 }
+
+///////// Archive
+
+/// Only the parts of an aggregate that are read back, which is all that is
+/// archived. Older archives kept the whole aggregate and still read as this.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedAggregate {
+    pub fetched_at: DateTime<Utc>,
+    pub monte_carlo_summary: Vec<ArchivedSummary>,
+}
+
+impl ArchivedAggregate {
+    pub fn from_aggregated(aggregated: &AggregatedStats) -> ArchivedAggregate {
+        return ArchivedAggregate {
+            fetched_at: aggregated.fetched_at,
+            monte_carlo_summary: aggregated
+                .monte_carlo_summary
+                .iter()
+                .map(|summary| ArchivedSummary {
+                    party: summary.party.clone(),
+                    median: summary.median,
+                    lower_5th: summary.lower_5th,
+                    upper_95th: summary.upper_95th,
+                    majority_percentage: summary.majority_percentage,
+                })
+                .collect(),
+        };
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchivedSummary {
+    pub party: PartyName,
+    pub median: i32,
+    pub lower_5th: i32,
+    pub upper_95th: i32,
+    pub majority_percentage: f64,
+}
+
+///////// Time series
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TimeSeries {
+    pub constituencies: Vec<ConstituencyTimeSeries>,
+    pub parties: Vec<PartyTimeSeries>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConstituencyTimeSeries {
+    pub ons_code: String,
+    /// The name in the most recent run.
+    pub constituency: String,
+    pub points: Vec<ConstituencyTimePoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConstituencyTimePoint {
+    pub fetched_at: DateTime<Utc>,
+    pub favourite: PartyName,
+    pub favourite_probability: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PartyTimeSeries {
+    pub party: PartyName,
+    pub points: Vec<PartyTimePoint>,
+}
+
+/// The monte carlo figures are missing for any run which wasn't aggregated.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PartyTimePoint {
+    pub fetched_at: DateTime<Utc>,
+    pub favourite_seats: i32,
+    pub median: Option<i32>,
+    pub majority_percentage: Option<f64>,
+}
+
+///////// Trajectories
+
+/// How each answer's probability has moved, rebuilt from the bet history.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Trajectories {
+    pub fetched_at: DateTime<Utc>,
+    pub constituencies: Vec<ConstituencyTrajectory>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConstituencyTrajectory {
+    pub ons_code: String,
+    pub constituency: String,
+    pub answers: Vec<AnswerTrajectory>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AnswerTrajectory {
+    pub party: PartyName,
+    /// The answer's text on Manifold.
+    pub answer: String,
+    /// Oldest first, ending with the probability at `fetched_at`.
+    pub points: Vec<TrajectoryPoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrajectoryPoint {
+    pub time: DateTime<Utc>,
+    pub probability: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Mover {
+    pub ons_code: String,
+    pub constituency: String,
+    pub party: PartyName,
+    pub probability_before: f64,
+    pub probability_now: f64,
+}
+
+///////// Changes
+
+/// What moved between two runs, see `diff::change_report`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChangeReport {
+    pub before_fetched_at: DateTime<Utc>,
+    pub after_fetched_at: DateTime<Utc>,
+    pub flipped_seats: Vec<FlippedSeat>,
+    /// The largest changes first.
+    pub probability_changes: Vec<ProbabilityChange>,
+    /// Empty unless both runs were aggregated.
+    pub simulation_changes: Vec<SimulationChange>,
+    pub added_markets: Vec<MarketChange>,
+    pub removed_markets: Vec<MarketChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FlippedSeat {
+    pub ons_code: String,
+    pub constituency: String,
+    pub favourite_before: PartyName,
+    pub favourite_after: PartyName,
+    pub probability_before: f64,
+    pub probability_after: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProbabilityChange {
+    pub ons_code: String,
+    pub constituency: String,
+    pub party: PartyName,
+    pub probability_before: f64,
+    pub probability_after: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SimulationChange {
+    pub party: PartyName,
+    /// `None` if the party won no seats in that run.
+    pub median_before: Option<i32>,
+    pub median_after: Option<i32>,
+    pub majority_percentage_before: Option<f64>,
+    pub majority_percentage_after: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MarketChange {
+    pub ons_code: String,
+    pub constituency: String,
+    pub manifold_url: String,
+}
+
+///////// Calibration
+
+/// How well the markets predicted the actual results.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CalibrationReport {
+    /// When the scored markets were fetched.
+    pub fetched_at: DateTime<Utc>,
+    pub seats_scored: usize,
+    /// The multi-class Brier score averaged over seats, 0 is perfect and 2 is
+    /// as wrong as possible.
+    pub brier_score: f64,
+    /// The mean of `-ln(p)` for the probability given to each seat's winner.
+    pub log_loss: f64,
+    pub reliability: Vec<ReliabilityBin>,
+    /// Empty unless simulation results were given.
+    pub seat_intervals: Vec<SeatIntervalCheck>,
+}
+
+/// Every (seat, party) probability in `[lower, upper)`, and how often those
+/// parties actually won.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReliabilityBin {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+    pub mean_predicted: f64,
+    pub observed_frequency: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SeatIntervalCheck {
+    pub party: PartyName,
+    pub actual_seats: i32,
+    pub median: i32,
+    pub lower_5th: i32,
+    pub upper_95th: i32,
+    pub within_interval: bool,
+}
+
+///////// Charts
+
+/// The html crate has no SVG elements, but doesn't escape text either, so an
+/// SVG goes inline as the only text of a `div`. `label` is read out in place
+/// of the chart.
+pub fn inline_svg(svg: &str, label: &str) -> html::text_content::Division {
+    let labelled = svg.replacen(
+        "<svg ",
+        &format!("<svg role='img' aria-label='{}' ", escape_attribute(label)),
+        1,
+    );
+    let mut division = html::text_content::Division::builder();
+    division.text(labelled);
+    return division.build();
+}
+
+fn escape_attribute(text: &str) -> String {
+    return text
+        .replace('&', "&amp;")
+        .replace('\'', "&#39;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+}
@@ -0,0 +1,598 @@
+use rand::Rng;
+use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.manifold.markets/v0";
+
+#[derive(Debug, Clone)]
+pub struct ManifoldClientConfig {
+    pub base_url: String,
+    /// How many requests may be in flight at once.
+    pub concurrency: usize,
+    /// How many markets to ask for in each page of a listing, at most 1000.
+    pub page_size: usize,
+    /// How many times a failed request is retried before giving up.
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub timeout: Duration,
+    pub snapshot: SnapshotMode,
+}
+
+/// Whether raw API responses are saved to, or served from, a snapshot directory.
+#[derive(Debug, Clone)]
+pub enum SnapshotMode {
+    /// Talk to the API and don't save anything.
+    Live,
+    /// Talk to the API and write every successful response into the directory.
+    Record(PathBuf),
+    /// Never touch the network, read every response from the directory instead.
+    Replay(PathBuf),
+}
+
+impl Default for ManifoldClientConfig {
+    fn default() -> Self {
+        return ManifoldClientConfig {
+            base_url: DEFAULT_BASE_URL.to_string(),
+            concurrency: 8,
+            page_size: 1000,
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            timeout: Duration::from_secs(30),
+            snapshot: SnapshotMode::Live,
+        };
+    }
+}
+
+#[derive(Debug)]
+pub enum ManifoldError {
+    /// The request couldn't be sent or the response couldn't be read.
+    Request { url: String, source: reqwest::Error },
+    /// Manifold responded with an error status.
+    Status { url: String, status: u16 },
+    /// The response wasn't the JSON we expected.
+    Decode {
+        url: String,
+        source: serde_json::Error,
+    },
+    /// A snapshot file couldn't be read or written.
+    Snapshot {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+impl std::fmt::Display for ManifoldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifoldError::Request { url, source } => {
+                write!(f, "request to {} failed: {}", url, source)
+            }
+            ManifoldError::Status { url, status } => {
+                write!(f, "request to {} returned status {}", url, status)
+            }
+            ManifoldError::Decode { url, source } => {
+                write!(f, "could not decode response from {}: {}", url, source)
+            }
+            ManifoldError::Snapshot { path, source } => {
+                write!(f, "snapshot file {} failed: {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ManifoldError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ManifoldError::Request { source, .. } => Some(source),
+            ManifoldError::Status { .. } => None,
+            ManifoldError::Decode { source, .. } => Some(source),
+            ManifoldError::Snapshot { source, .. } => Some(source),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Market {
+    pub id: String,
+    pub question: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketDetailed {
+    pub answers: Vec<MarketAnswer>,
+    pub url: String,
+    #[serde(default)]
+    pub volume: f64,
+    #[serde(default)]
+    pub total_liquidity: f64,
+    #[serde(default)]
+    pub unique_bettor_count: u32,
+    /// Milliseconds since the epoch, like all of Manifold's times.
+    pub created_time: Option<i64>,
+    pub close_time: Option<i64>,
+    pub last_bet_time: Option<i64>,
+    #[serde(default)]
+    pub is_resolved: bool,
+    pub resolution: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MarketAnswer {
+    #[serde(default)]
+    pub id: String,
+    pub text: String,
+    pub probability: f64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Bet {
+    pub id: String,
+    /// Which answer of a multiple choice market the bet was on.
+    pub answer_id: Option<String>,
+    /// Milliseconds since the epoch.
+    pub created_time: i64,
+    pub prob_before: f64,
+    pub prob_after: f64,
+}
+
+pub struct ManifoldClient {
+    config: ManifoldClientConfig,
+    client: reqwest::blocking::Client,
+    pool: rayon::ThreadPool,
+}
+
+impl ManifoldClient {
+    pub fn new(config: ManifoldClientConfig) -> ManifoldClient {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .unwrap();
+        // requests run on their own pool so that the concurrency is bounded
+        // whatever the global rayon pool looks like
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(config.concurrency.max(1))
+            .build()
+            .unwrap();
+        return ManifoldClient {
+            config,
+            client,
+            pool,
+        };
+    }
+
+    /// List every market in the group.
+    pub fn get_all_markets_in_group(&self, group_id: &str) -> Result<Vec<Market>, ManifoldError> {
+        return self.get_paginated(
+            &format!("markets?groupId={}", group_id),
+            |market: &Market| &market.id,
+        );
+    }
+
+    pub fn get_market(&self, market_id: &str) -> Result<MarketDetailed, ManifoldError> {
+        return self.get_json(&format!("market/{}", market_id));
+    }
+
+    /// Fetch every market, at most `concurrency` at a time. A market which
+    /// fails doesn't stop the others, its error is returned in its place.
+    pub fn get_markets(
+        &self,
+        market_ids: &[String],
+    ) -> Vec<(String, Result<MarketDetailed, ManifoldError>)> {
+        return self.pool.install(|| {
+            market_ids
+                .par_iter()
+                .map(|market_id| (market_id.clone(), self.get_market(market_id)))
+                .collect()
+        });
+    }
+
+    /// Every bet on a market, newest first.
+    pub fn get_bets(&self, market_id: &str) -> Result<Vec<Bet>, ManifoldError> {
+        return self.get_paginated(&format!("bets?contractId={}", market_id), |bet: &Bet| {
+            &bet.id
+        });
+    }
+
+    /// Fetch the bets on every market, at most `concurrency` markets at a time.
+    pub fn get_bets_for_markets(
+        &self,
+        market_ids: &[String],
+    ) -> Vec<(String, Result<Vec<Bet>, ManifoldError>)> {
+        return self.pool.install(|| {
+            market_ids
+                .par_iter()
+                .map(|market_id| (market_id.clone(), self.get_bets(market_id)))
+                .collect()
+        });
+    }
+
+    /// Every item of a listing, following the `before` cursor (the id of the
+    /// last item on the previous page) until Manifold runs out of pages.
+    fn get_paginated<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        id_of: impl Fn(&T) -> &str,
+    ) -> Result<Vec<T>, ManifoldError> {
+        let mut items: Vec<T> = Vec::new();
+        let mut seen_ids: HashSet<String> = HashSet::new();
+        let mut before: Option<String> = None;
+        loop {
+            let mut page_path = format!("{}&limit={}", path, self.config.page_size);
+            if let Some(before) = &before {
+                page_path = format!("{}&before={}", page_path, before);
+            }
+            let page: Vec<T> = self.get_json(&page_path)?;
+            let page_length = page.len();
+            let last_id = page.last().map(|item| id_of(item).to_string());
+
+            // pages can overlap if items are created while we're listing
+            let mut new_items = 0;
+            for item in page {
+                if seen_ids.insert(id_of(&item).to_string()) {
+                    items.push(item);
+                    new_items += 1;
+                }
+            }
+
+            if page_length < self.config.page_size || new_items == 0 {
+                break;
+            }
+            before = last_id;
+        }
+        return Ok(items);
+    }
+
+    /// GET a path relative to the base url and decode the JSON response,
+    /// going via the snapshot directory if there is one.
+    pub fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, ManifoldError> {
+        let url = format!("{}/{}", self.config.base_url.trim_end_matches('/'), path);
+        let body = match &self.config.snapshot {
+            SnapshotMode::Live => self.get_body(&url)?,
+            SnapshotMode::Record(directory) => {
+                let body = self.get_body(&url)?;
+                let snapshot_path = directory.join(snapshot_file_name(path));
+                std::fs::create_dir_all(directory)
+                    .and_then(|_| std::fs::write(&snapshot_path, &body))
+                    .map_err(|source| ManifoldError::Snapshot {
+                        path: snapshot_path,
+                        source,
+                    })?;
+                body
+            }
+            SnapshotMode::Replay(directory) => {
+                let snapshot_path = directory.join(snapshot_file_name(path));
+                std::fs::read_to_string(&snapshot_path).map_err(|source| {
+                    ManifoldError::Snapshot {
+                        path: snapshot_path,
+                        source,
+                    }
+                })?
+            }
+        };
+        return serde_json::from_str(&body).map_err(|source| ManifoldError::Decode { url, source });
+    }
+
+    /// GET a url, retrying rate limits, server errors and connection problems
+    /// with exponential backoff.
+    fn get_body(&self, url: &str) -> Result<String, ManifoldError> {
+        let url = url.to_string();
+        let mut attempt = 0;
+        loop {
+            let (error, retry_after) = match self.client.get(&url).send() {
+                Ok(response) if response.status().is_success() => {
+                    return response.text().map_err(|source| ManifoldError::Request {
+                        url: url.clone(),
+                        source,
+                    });
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = response
+                        .headers()
+                        .get(reqwest::header::RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| parse_retry_after(value, chrono::Utc::now()));
+                    let error = ManifoldError::Status {
+                        url: url.clone(),
+                        status: status.as_u16(),
+                    };
+                    let is_retryable = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                        || status.is_server_error();
+                    if !is_retryable {
+                        return Err(error);
+                    }
+                    (error, retry_after)
+                }
+                Err(source) => (
+                    ManifoldError::Request {
+                        url: url.clone(),
+                        source,
+                    },
+                    None,
+                ),
+            };
+
+            if attempt >= self.config.max_retries {
+                return Err(error);
+            }
+            // don't let the server keep us waiting longer than we'd back off
+            let wait = retry_after
+                .map(|retry_after| retry_after.min(self.config.max_backoff))
+                .unwrap_or_else(|| self.backoff(attempt));
+            std::thread::sleep(wait);
+            attempt += 1;
+        }
+    }
+
+    /// Exponential backoff with full jitter.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .config
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.config.max_backoff);
+        let millis = rand::thread_rng().gen_range(0..=ceiling.as_millis() as u64);
+        return Duration::from_millis(millis);
+    }
+}
+
+/// How long a `Retry-After` header asks us to wait, given either as a number
+/// of seconds or as an HTTP date. A date in the past means no wait.
+fn parse_retry_after(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    return Some(
+        (date.with_timezone(&chrono::Utc) - now)
+            .to_std()
+            .unwrap_or_default(),
+    );
+}
+
+/// The file a response is stored under in a snapshot directory, e.g.
+/// `market/abc` becomes `market_abc.json`.
+fn snapshot_file_name(path: &str) -> String {
+    let name: String = path
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    return format!("{}.json", name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    /// A canned response: the status, extra headers and body.
+    type MockResponse = (u16, Vec<(&'static str, String)>, String);
+
+    /// A local HTTP server answering every request with `respond`, given the
+    /// path (without the leading `/`) and how many requests came before it.
+    struct MockServer {
+        base_url: String,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MockServer {
+        fn start(respond: impl Fn(&str, usize) -> MockResponse + Send + 'static) -> MockServer {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let base_url = format!("http://{}", listener.local_addr().unwrap());
+            let requests: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+            let seen = requests.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+                    // skip the rest of the head
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                    }
+                    let path = request_line
+                        .split_whitespace()
+                        .nth(1)
+                        .unwrap_or("/")
+                        .trim_start_matches('/')
+                        .to_string();
+                    let count = {
+                        let mut seen = seen.lock().unwrap();
+                        seen.push(path.clone());
+                        seen.len() - 1
+                    };
+                    let (status, headers, body) = respond(&path, count);
+                    let mut response = format!(
+                        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+                        status,
+                        body.len()
+                    );
+                    for (name, value) in headers {
+                        response.push_str(&format!("{}: {}\r\n", name, value));
+                    }
+                    response.push_str("\r\n");
+                    response.push_str(&body);
+                    let _ = stream.write_all(response.as_bytes());
+                }
+            });
+            return MockServer { base_url, requests };
+        }
+
+        fn client(&self) -> ManifoldClient {
+            return ManifoldClient::new(ManifoldClientConfig {
+                base_url: self.base_url.clone(),
+                concurrency: 2,
+                max_retries: 3,
+                initial_backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(20),
+                timeout: Duration::from_secs(5),
+                ..ManifoldClientConfig::default()
+            });
+        }
+
+        fn requests(&self) -> Vec<String> {
+            return self.requests.lock().unwrap().clone();
+        }
+    }
+
+    fn market_json() -> String {
+        return r#"{"answers": [{"text": "Labour", "probability": 0.6}], "url": "https://manifold.markets/m"}"#
+            .to_string();
+    }
+
+    fn status(status: u16) -> MockResponse {
+        return (status, Vec::new(), "{}".to_string());
+    }
+
+    #[test]
+    fn retries_rate_limits_and_server_errors() {
+        let server = MockServer::start(|_, count| match count {
+            0 => status(429),
+            1 => status(502),
+            _ => (200, Vec::new(), market_json()),
+        });
+        let market = server.client().get_market("abc").unwrap();
+        assert_eq!(market.answers[0].text, "Labour");
+        assert_eq!(server.requests(), vec!["market/abc"; 3]);
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let server = MockServer::start(|_, _| status(503));
+        match server.client().get_market("abc") {
+            Err(ManifoldError::Status { status: 503, .. }) => {}
+            other => panic!("expected a 503, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[test]
+    fn does_not_retry_client_errors() {
+        let server = MockServer::start(|_, _| status(404));
+        assert!(server.client().get_market("abc").is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn retry_after_is_clamped_to_max_backoff() {
+        let server = MockServer::start(|_, count| match count {
+            0 => (
+                429,
+                vec![("Retry-After", "3600".to_string())],
+                "{}".to_string(),
+            ),
+            1 => (
+                503,
+                vec![("Retry-After", "Fri, 31 Dec 2100 23:59:59 GMT".to_string())],
+                "{}".to_string(),
+            ),
+            _ => (200, Vec::new(), market_json()),
+        });
+        let start = Instant::now();
+        server.client().get_market("abc").unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn parses_both_forms_of_retry_after() {
+        let now = chrono::DateTime::parse_from_rfc3339("2024-07-04T22:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert_eq!(
+            parse_retry_after(" 120 ", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Thu, 04 Jul 2024 22:00:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Thu, 04 Jul 2024 21:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn get_markets_collects_each_failure() {
+        let server = MockServer::start(|path, _| match path {
+            "market/good" => (200, Vec::new(), market_json()),
+            "market/missing" => status(404),
+            _ => (200, Vec::new(), "not json".to_string()),
+        });
+        let ids: Vec<String> = ["good", "missing", "broken"]
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        let results = server.client().get_markets(&ids);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].0, "good");
+        assert!(results[0].1.is_ok());
+        assert!(matches!(
+            results[1].1,
+            Err(ManifoldError::Status { status: 404, .. })
+        ));
+        assert!(matches!(results[2].1, Err(ManifoldError::Decode { .. })));
+    }
+
+    #[test]
+    fn follows_the_before_cursor_and_drops_overlaps() {
+        let markets = |ids: &[&str]| {
+            let markets: Vec<String> = ids
+                .iter()
+                .map(|id| format!(r#"{{"id": "{}", "question": "{}?"}}"#, id, id))
+                .collect();
+            return (200, Vec::new(), format!("[{}]", markets.join(",")));
+        };
+        let server = MockServer::start(move |path, _| match path {
+            "markets?groupId=uk&limit=2" => markets(&["a", "b"]),
+            // a market created while listing pushes `b` onto the next page
+            "markets?groupId=uk&limit=2&before=b" => markets(&["b", "c"]),
+            "markets?groupId=uk&limit=2&before=c" => markets(&["d"]),
+            _ => status(404),
+        });
+        let client = ManifoldClient::new(ManifoldClientConfig {
+            page_size: 2,
+            ..server.client().config
+        });
+        let ids: Vec<String> = client
+            .get_all_markets_in_group("uk")
+            .unwrap()
+            .into_iter()
+            .map(|market| market.id)
+            .collect();
+        assert_eq!(ids, vec!["a", "b", "c", "d"]);
+        assert_eq!(
+            server.requests(),
+            vec![
+                "markets?groupId=uk&limit=2",
+                "markets?groupId=uk&limit=2&before=b",
+                "markets?groupId=uk&limit=2&before=c",
+            ]
+        );
+    }
+}
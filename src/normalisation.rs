@@ -0,0 +1,140 @@
+use crate::{NormalisationStrategy, Party};
+
+/// The sum of a market's answer probabilities, which needn't be exactly 1.
+pub fn overround(parties: &[Party]) -> f64 {
+    return parties.iter().map(|party| party.probability).sum();
+}
+
+pub fn is_overround_flagged(overround: f64, threshold: f64) -> bool {
+    return (overround - 1.0).abs() > threshold;
+}
+
+/// Rescale a market's probabilities so that they sum to 1.
+pub fn normalise_probabilities(parties: &[Party], strategy: &NormalisationStrategy) -> Vec<Party> {
+    let total = overround(parties);
+    if total <= 0.0 {
+        return parties.to_vec();
+    }
+    return match strategy {
+        NormalisationStrategy::None => parties.to_vec(),
+        NormalisationStrategy::Proportional => parties
+            .iter()
+            .map(|party| Party {
+                name: party.name.clone(),
+                probability: party.probability / total,
+            })
+            .collect(),
+        NormalisationStrategy::Power => {
+            let exponent = find_power_exponent(parties);
+            parties
+                .iter()
+                .map(|party| Party {
+                    name: party.name.clone(),
+                    probability: party.probability.powf(exponent),
+                })
+                .collect()
+        }
+    };
+}
+
+/// Find `k` such that the probabilities raised to the power `k` sum to 1. An
+/// overround market gets `k > 1`, which takes proportionally more away from the
+/// longshots than the favourite.
+fn find_power_exponent(parties: &[Party]) -> f64 {
+    let sum_with_exponent = |exponent: f64| -> f64 {
+        parties
+            .iter()
+            .filter(|party| party.probability > 0.0)
+            .map(|party| party.probability.powf(exponent))
+            .sum()
+    };
+    // the sum is decreasing in the exponent, so bisect
+    let mut low = 0.0;
+    let mut high = 1.0;
+    while sum_with_exponent(high) > 1.0 && high < 1024.0 {
+        high *= 2.0;
+    }
+    for _ in 0..100 {
+        let middle = (low + high) / 2.0;
+        if sum_with_exponent(middle) > 1.0 {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    return (low + high) / 2.0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PartyName;
+
+    fn market(probabilities: &[f64]) -> Vec<Party> {
+        let names = [
+            PartyName::Labour,
+            PartyName::Conservatives,
+            PartyName::LiberalDemocrats,
+            PartyName::Reform,
+        ];
+        return names
+            .iter()
+            .zip(probabilities)
+            .map(|(name, probability)| Party {
+                name: name.clone(),
+                probability: *probability,
+            })
+            .collect();
+    }
+
+    fn probabilities(parties: &[Party]) -> Vec<f64> {
+        return parties.iter().map(|party| party.probability).collect();
+    }
+
+    #[test]
+    fn proportional_scaling_sums_to_one() {
+        let normalised = normalise_probabilities(
+            &market(&[0.6, 0.3, 0.2, 0.1]),
+            &NormalisationStrategy::Proportional,
+        );
+        assert!((overround(&normalised) - 1.0).abs() < 1e-12);
+        let expected = [0.5, 0.25, 1.0 / 6.0, 1.0 / 12.0];
+        for (probability, expected) in probabilities(&normalised).iter().zip(expected) {
+            assert!((probability - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn power_normalisation_sums_to_one_and_keeps_the_order() {
+        for market in [market(&[0.6, 0.3, 0.2, 0.1]), market(&[0.5, 0.2, 0.1, 0.0])] {
+            let normalised = normalise_probabilities(&market, &NormalisationStrategy::Power);
+            assert!((overround(&normalised) - 1.0).abs() < 1e-9);
+            let before = probabilities(&market);
+            let after = probabilities(&normalised);
+            for i in 0..before.len() {
+                for j in 0..before.len() {
+                    if before[i] > before[j] {
+                        assert!(after[i] > after[j]);
+                    }
+                }
+            }
+        }
+        // an overround market takes proportionally more from the longshots
+        let normalised = normalise_probabilities(
+            &market(&[0.6, 0.3, 0.2, 0.1]),
+            &NormalisationStrategy::Power,
+        );
+        assert!(normalised[0].probability > 0.5);
+        assert!(normalised[3].probability < 1.0 / 12.0);
+    }
+
+    #[test]
+    fn flags_an_overround() {
+        let threshold = 0.05;
+        assert!((overround(&market(&[0.6, 0.3, 0.2, 0.1])) - 1.2).abs() < 1e-12);
+        assert!(is_overround_flagged(1.2, threshold));
+        assert!(is_overround_flagged(0.9, threshold));
+        assert!(!is_overround_flagged(1.03, threshold));
+        assert!(!is_overround_flagged(0.97, threshold));
+    }
+}
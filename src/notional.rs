@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use crate::parties::party_from_key;
+use crate::simulation::SimulationResults;
+use crate::{ConstituencyStatus, NotionalChange, NotionalComparison, PartyName, SeatTransition};
+
+/// Who would have won each constituency on the 2023 boundaries had the 2019
+/// election been fought on them, keyed by ONS code: the published notional
+/// results, totalling Con 372, Lab 200, SNP 48, LD 8, DUP 8, SF 7, PC 2,
+/// SDLP 2, Green 1, Alliance 1 and the Speaker. The winner is read with
+/// `party_from_key`, e.g. `Conservatives` or `Speaker`.
+const NOTIONAL_2019_TSV: &str = include_str!("../data/notional_2019.tsv");
+
+pub fn notional_2019_winners() -> &'static HashMap<String, PartyName> {
+    static WINNERS: OnceLock<HashMap<String, PartyName>> = OnceLock::new();
+    return WINNERS.get_or_init(|| {
+        NOTIONAL_2019_TSV
+            .lines()
+            .skip(1)
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let columns: Vec<&str> = line.split('\t').collect();
+                return (columns[0].to_string(), party_from_key(columns[2].trim()));
+            })
+            .collect()
+    });
+}
+
+pub fn notional_2019_winner(ons_code: &str) -> Option<&'static PartyName> {
+    return notional_2019_winners().get(ons_code);
+}
+
+/// Compare the favourites and the simulations against the notional 2019
+/// winners. Seats whose notional winner isn't standing anywhere are left
+/// out, as the simulations can't track them.
+pub fn notional_comparison(
+    constituencies: &[ConstituencyStatus],
+    monte_carlo_results: &SimulationResults,
+) -> NotionalComparison {
+    let parties = &monte_carlo_results.parties;
+
+    let mut favourite_gains: HashMap<&PartyName, i32> = HashMap::new();
+    let mut favourite_losses: HashMap<&PartyName, i32> = HashMap::new();
+    let mut favourite_transitions: BTreeMap<(&PartyName, &PartyName), i32> = BTreeMap::new();
+    for constituency in constituencies {
+        let notional_winner = match notional_2019_winner(&constituency.ons_code) {
+            Some(winner) if parties.contains(winner) => winner,
+            _ => continue,
+        };
+        let favourite = match constituency
+            .parties
+            .iter()
+            .max_by(|a, b| a.probability.partial_cmp(&b.probability).unwrap())
+        {
+            Some(favourite) => &favourite.name,
+            None => continue,
+        };
+        if favourite != notional_winner {
+            *favourite_gains.entry(favourite).or_insert(0) += 1;
+            *favourite_losses.entry(notional_winner).or_insert(0) += 1;
+            *favourite_transitions
+                .entry((notional_winner, favourite))
+                .or_insert(0) += 1;
+        }
+    }
+
+    let mut changes: Vec<NotionalChange> = parties
+        .iter()
+        .enumerate()
+        .map(|(index, party)| {
+            let gains = &monte_carlo_results.gains_histograms[index];
+            let losses = &monte_carlo_results.losses_histograms[index];
+            let covered = &monte_carlo_results.covered_histograms[index];
+            let notional_seats = monte_carlo_results.notional_seats[index] as i32;
+            return NotionalChange {
+                party: party.clone(),
+                notional_seats,
+                favourite_gains: favourite_gains.get(party).cloned().unwrap_or(0),
+                favourite_losses: favourite_losses.get(party).cloned().unwrap_or(0),
+                expected_gains: gains.mean(),
+                gains_lower_5th: gains.quantile(0.05),
+                gains_upper_95th: gains.quantile(0.95),
+                expected_losses: losses.mean(),
+                losses_lower_5th: losses.quantile(0.05),
+                losses_upper_95th: losses.quantile(0.95),
+                net_change_median: covered.median() - notional_seats,
+                net_change_lower_5th: covered.quantile(0.05) - notional_seats,
+                net_change_upper_95th: covered.quantile(0.95) - notional_seats,
+            };
+        })
+        .filter(|change| {
+            change.notional_seats > 0 || change.favourite_gains > 0 || change.expected_gains > 0.0
+        })
+        .collect();
+    changes.sort_by_key(|change| std::cmp::Reverse(change.net_change_median));
+
+    let number_of_simulations = monte_carlo_results.number_of_simulations().max(1) as f64;
+    let mut transitions: Vec<SeatTransition> = Vec::new();
+    for (from_index, from) in parties.iter().enumerate() {
+        for (to_index, to) in parties.iter().enumerate() {
+            let count = monte_carlo_results.transitions[from_index][to_index];
+            let favourite_seats = favourite_transitions.get(&(from, to)).cloned().unwrap_or(0);
+            if count == 0 && favourite_seats == 0 {
+                continue;
+            }
+            transitions.push(SeatTransition {
+                from: from.clone(),
+                to: to.clone(),
+                favourite_seats,
+                expected_seats: count as f64 / number_of_simulations,
+            });
+        }
+    }
+    transitions.sort_by(|a, b| b.expected_seats.partial_cmp(&a.expected_seats).unwrap());
+
+    return NotionalComparison {
+        seats_with_notional: monte_carlo_results.notional_seats.iter().sum(),
+        changes,
+        transitions,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constituencies::reference_constituencies;
+
+    #[test]
+    fn every_constituency_has_a_notional_winner() {
+        let winners = notional_2019_winners();
+        let missing: Vec<&str> = reference_constituencies()
+            .iter()
+            .filter(|reference| !winners.contains_key(&reference.ons_code))
+            .map(|reference| reference.name.as_str())
+            .collect();
+        assert!(missing.is_empty(), "no notional winner for {:?}", missing);
+        assert_eq!(winners.len(), reference_constituencies().len());
+    }
+
+    #[test]
+    fn matches_the_published_totals() {
+        let mut totals: HashMap<&PartyName, i32> = HashMap::new();
+        for winner in notional_2019_winners().values() {
+            *totals.entry(winner).or_insert(0) += 1;
+        }
+        let expected = [
+            (&PartyName::Conservatives, 372),
+            (&PartyName::Labour, 200),
+            (&PartyName::SNP, 48),
+            (&PartyName::LiberalDemocrats, 8),
+            (&PartyName::DUP, 8),
+            (&PartyName::SinnFein, 7),
+            (&PartyName::PlaidCymru, 2),
+            (&PartyName::SDLP, 2),
+            (&PartyName::Green, 1),
+            (&PartyName::Alliance, 1),
+            (&PartyName::Speaker, 1),
+        ];
+        assert_eq!(totals, expected.into_iter().collect());
+    }
+}
@@ -0,0 +1,194 @@
+use serde::{Deserialize, Deserializer};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use crate::constituencies::normalise_name;
+use crate::{Nation, PartyInfo, PartyName};
+
+/// Display names, colours etc. for every party we know about.
+const PARTIES_JSON: &str = include_str!("../data/parties.json");
+
+/// The colour used for parties which aren't in the registry.
+const UNKNOWN_PARTY_COLOUR: &str = "#999999";
+
+/// The aliases used when no config file is given.
+const DEFAULT_PARTY_ALIASES_JSON: &str = include_str!("../data/party_aliases.json");
+
+/// The shape of a party alias config file, e.g.
+/// `{"aliases": {"LiberalDemocrats": ["Lib Dem"]}, "prefixes": {"Independent": ["Independent"]}}`.
+/// The keys are read with `party_from_key`, so a party can be given aliases
+/// before it has a `PartyName` variant.
+#[derive(Debug, Deserialize)]
+pub struct PartyAliasConfig {
+    /// Answer texts which name the party exactly.
+    pub aliases: BTreeMap<String, Vec<String>>,
+    /// Answer texts which name the party if they start with one of these,
+    /// e.g. `Independent (Jeremy Corbyn)`.
+    #[serde(default)]
+    pub prefixes: BTreeMap<String, Vec<String>>,
+}
+
+/// The party named by a key in one of the data files, e.g. `Labour`. A key
+/// which isn't a `PartyName` variant, e.g. for a party added to
+/// `data/parties.json`, becomes `Registered` with the key as its name.
+pub fn party_from_key(key: &str) -> PartyName {
+    return serde_json::from_value(serde_json::Value::String(key.to_string()))
+        .unwrap_or_else(|_| PartyName::Registered(key.to_string()));
+}
+
+/// Read a party either as a key (see `party_from_key`) or as `PartyName`
+/// serialises itself, so that registry entries round trip.
+pub fn deserialize_party_key<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<PartyName, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    return match value {
+        serde_json::Value::String(key) => Ok(party_from_key(&key)),
+        value => serde_json::from_value(value).map_err(serde::de::Error::custom),
+    };
+}
+
+/// Turns the answer texts of a market into parties. Aliases are compared
+/// after normalising case, punctuation and accents.
+pub struct PartyAliases {
+    aliases: HashMap<String, PartyName>,
+    prefixes: Vec<(String, PartyName)>,
+}
+
+impl PartyAliases {
+    pub fn new(config: PartyAliasConfig) -> PartyAliases {
+        let mut aliases = HashMap::new();
+        for (party, names) in config.aliases {
+            let party = party_from_key(&party);
+            for name in names {
+                aliases.insert(normalise_name(&name), party.clone());
+            }
+        }
+        let mut prefixes = Vec::new();
+        for (party, names) in config.prefixes {
+            let party = party_from_key(&party);
+            for name in names {
+                prefixes.push((normalise_name(&name), party.clone()));
+            }
+        }
+        // try the longest prefix first, so the most specific one wins
+        prefixes.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then(a.0.cmp(&b.0)));
+        return PartyAliases { aliases, prefixes };
+    }
+
+    pub fn from_json(json: &str) -> Result<PartyAliases, serde_json::Error> {
+        let config: PartyAliasConfig = serde_json::from_str(json)?;
+        return Ok(PartyAliases::new(config));
+    }
+
+    /// Load the aliases from a JSON file, or the built in ones if there's no file.
+    pub fn load(path: Option<&str>) -> PartyAliases {
+        return match path {
+            Some(path) => {
+                let json = std::fs::read_to_string(path)
+                    .unwrap_or_else(|error| panic!("could not read {}: {}", path, error));
+                PartyAliases::from_json(&json)
+                    .unwrap_or_else(|error| panic!("could not parse {}: {}", path, error))
+            }
+            None => PartyAliases::from_json(DEFAULT_PARTY_ALIASES_JSON).unwrap(),
+        };
+    }
+
+    pub fn parse(&self, answer: &str) -> PartyName {
+        let normalised = normalise_name(answer);
+        if let Some(party) = self.aliases.get(&normalised) {
+            return party.clone();
+        }
+        for (prefix, party) in &self.prefixes {
+            if normalised == *prefix || normalised.starts_with(&format!("{} ", prefix)) {
+                return party.clone();
+            }
+        }
+        return PartyName::Unparsed(answer.trim().to_string());
+    }
+}
+
+pub fn party_registry() -> &'static [PartyInfo] {
+    static PARTIES: OnceLock<Vec<PartyInfo>> = OnceLock::new();
+    return PARTIES.get_or_init(|| serde_json::from_str(PARTIES_JSON).unwrap());
+}
+
+/// Whether a party is one we know about: anything but an answer no alias
+/// matched, including parties only named in the data files.
+pub fn is_known_party(party: &PartyName) -> bool {
+    return !matches!(party, PartyName::Unparsed(_));
+}
+
+/// The registry entry for a party. Parties which aren't in the registry,
+/// e.g. unparsed answers, get a plain grey entry named after themselves.
+pub fn party_info(party: &PartyName) -> PartyInfo {
+    if let Some(info) = party_registry().iter().find(|info| info.party == *party) {
+        return info.clone();
+    }
+    let name = match party {
+        PartyName::Registered(name) | PartyName::Unparsed(name) => name.clone(),
+        _ => format!("{:?}", party),
+    };
+    return PartyInfo {
+        party: party.clone(),
+        abbreviation: name.chars().take(3).collect(),
+        display_name: name,
+        colour: UNKNOWN_PARTY_COLOUR.to_string(),
+        emoji: "".to_string(),
+        nations: vec![
+            Nation::England,
+            Nation::Scotland,
+            Nation::Wales,
+            Nation::NorthernIreland,
+        ],
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_parties_without_a_variant() {
+        let registry: Vec<PartyInfo> = serde_json::from_str(
+            r##"[
+                {"party": "Labour", "display_name": "Labour", "abbreviation": "Lab", "colour": "#E4003B", "emoji": "", "nations": ["England"]},
+                {"party": "Workers", "display_name": "Workers Party", "abbreviation": "WPB", "colour": "#770000", "emoji": "", "nations": ["England"]}
+            ]"##,
+        )
+        .unwrap();
+        assert_eq!(registry[0].party, PartyName::Labour);
+        assert_eq!(
+            registry[1].party,
+            PartyName::Registered("Workers".to_string())
+        );
+        assert!(is_known_party(&registry[1].party));
+        // and they survive being written out and read back
+        let round_trip: Vec<PartyInfo> =
+            serde_json::from_str(&serde_json::to_string(&registry).unwrap()).unwrap();
+        assert_eq!(round_trip[1].party, registry[1].party);
+
+        let aliases = PartyAliases::from_json(
+            r#"{"aliases": {"Labour": ["Labour Party"], "Workers": ["Workers Party of Britain"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(aliases.parse("labour party"), PartyName::Labour);
+        assert_eq!(
+            aliases.parse("Workers Party of Britain"),
+            PartyName::Registered("Workers".to_string())
+        );
+        // an alias is enough to know a party, even without a registry entry
+        let aliases =
+            PartyAliases::from_json(r#"{"aliases": {"Yorkshire": ["Yorkshire Party"]}}"#).unwrap();
+        let yorkshire = aliases.parse("Yorkshire Party");
+        assert_eq!(yorkshire, PartyName::Registered("Yorkshire".to_string()));
+        assert!(is_known_party(&yorkshire));
+        assert_eq!(yorkshire.to_string(), "Yorkshire");
+        let unknown = aliases.parse("Monster Raving Loony");
+        assert_eq!(
+            unknown,
+            PartyName::Unparsed("Monster Raving Loony".to_string())
+        );
+        assert!(!is_known_party(&unknown));
+    }
+}
@@ -0,0 +1,240 @@
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+use crate::constituencies::find_by_ons_code;
+use crate::{ConstituencyStatus, MarketMetadata, Party, PartyName, ThinMarketAdjustment};
+
+/// The volume (in mana) at which a market counts as about two-thirds liquid.
+const VOLUME_SCALE: f64 = 1000.0;
+/// The number of traders at which a market counts as about two-thirds well
+/// traded.
+const BETTOR_SCALE: f64 = 10.0;
+/// How many days without a bet before a market counts as about two-thirds stale.
+const STALENESS_DAYS: f64 = 14.0;
+
+/// A score between 0 and 1 for how far a market's prices can be trusted,
+/// from its volume, how many people have traded on it and how recently.
+/// Each part saturates, and the score is their geometric mean, so a market
+/// has to do well on all three.
+pub fn market_quality(market: &MarketMetadata, now: &DateTime<Utc>) -> f64 {
+    let volume_score = 1.0 - (-market.volume.max(0.0) / VOLUME_SCALE).exp();
+    let bettor_score = 1.0 - (-(market.unique_bettors as f64) / BETTOR_SCALE).exp();
+    let freshness_score = match market.last_bet_time {
+        Some(last_bet_time) => {
+            let days_since = (*now - last_bet_time).num_seconds().max(0) as f64 / 86400.0;
+            (-days_since / STALENESS_DAYS).exp()
+        }
+        None => 0.0,
+    };
+    return (volume_score * bettor_score * freshness_score).cbrt();
+}
+
+pub fn is_thin_market(quality: f64, threshold: f64) -> bool {
+    return quality < threshold;
+}
+
+/// Pull the probabilities of markets below the quality threshold toward the
+/// average of the other markets in the same region (or nation), in
+/// proportion to how far below the threshold they are. Markets above the
+/// threshold are left alone.
+pub fn shrink_thin_markets(
+    constituencies: &[ConstituencyStatus],
+    qualities: &[f64],
+    threshold: f64,
+    adjustment: &ThinMarketAdjustment,
+) -> Vec<ConstituencyStatus> {
+    let group_of = |constituency: &ConstituencyStatus| -> Option<String> {
+        let reference = find_by_ons_code(&constituency.ons_code)?;
+        return match adjustment {
+            ThinMarketAdjustment::None => None,
+            ThinMarketAdjustment::ShrinkToRegion => Some(reference.region.clone()),
+            ThinMarketAdjustment::ShrinkToNation => Some(reference.nation.to_string()),
+        };
+    };
+
+    // the priors are weighted by quality, so that deep markets dominate
+    let mut totals: BTreeMap<String, (BTreeMap<PartyName, f64>, f64)> = BTreeMap::new();
+    for (constituency, quality) in constituencies.iter().zip(qualities) {
+        let group = match group_of(constituency) {
+            Some(group) => group,
+            None => continue,
+        };
+        let (party_totals, total_weight) = totals.entry(group).or_default();
+        for party in &constituency.parties {
+            *party_totals.entry(party.name.clone()).or_insert(0.0) += quality * party.probability;
+        }
+        *total_weight += quality;
+    }
+
+    return constituencies
+        .iter()
+        .zip(qualities)
+        .map(|(constituency, quality)| {
+            let prior = group_of(constituency).and_then(|group| totals.get(&group));
+            let party_totals = match prior {
+                Some((party_totals, total_weight)) if *total_weight > 0.0 => party_totals,
+                _ => return constituency.clone(),
+            };
+            if !is_thin_market(*quality, threshold) {
+                return constituency.clone();
+            }
+            let weight = quality / threshold;
+            // only the parties actually standing, renormalised to the seat's
+            // own total so that shrinking doesn't change it
+            let seat_total: f64 = constituency
+                .parties
+                .iter()
+                .map(|party| party.probability)
+                .sum();
+            let prior_total: f64 = constituency
+                .parties
+                .iter()
+                .map(|party| party_totals.get(&party.name).cloned().unwrap_or(0.0))
+                .sum();
+            let parties = constituency
+                .parties
+                .iter()
+                .map(|party| {
+                    let prior_probability = if prior_total > 0.0 {
+                        party_totals.get(&party.name).cloned().unwrap_or(0.0) / prior_total
+                            * seat_total
+                    } else {
+                        party.probability
+                    };
+                    return Party {
+                        name: party.name.clone(),
+                        probability: weight * party.probability
+                            + (1.0 - weight) * prior_probability,
+                    };
+                })
+                .collect();
+            return ConstituencyStatus {
+                parties,
+                ..constituency.clone()
+            };
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::constituency_with_code;
+    use chrono::Duration;
+
+    fn market(volume: f64, unique_bettors: u32, days_since_bet: Option<i64>) -> MarketMetadata {
+        return MarketMetadata {
+            volume,
+            unique_bettors,
+            last_bet_time: days_since_bet.map(|days| now() - Duration::days(days)),
+            ..MarketMetadata::default()
+        };
+    }
+
+    fn now() -> DateTime<Utc> {
+        return DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+    }
+
+    #[test]
+    fn quality_saturates() {
+        let deep = market_quality(&market(1e6, 1000, Some(0)), &now());
+        assert!(deep > 0.999 && deep <= 1.0);
+        // more of everything always helps, but by less and less
+        let some = market_quality(&market(1000.0, 10, Some(14)), &now());
+        let more = market_quality(&market(2000.0, 20, Some(7)), &now());
+        let lots = market_quality(&market(4000.0, 40, Some(0)), &now());
+        assert!(some < more && more < lots && lots < deep);
+        assert!(more - some > lots - more);
+        // one part at its scale is 1 - 1/e, or 1/e for staleness
+        let expected = ((1.0 - (-1.0_f64).exp()).powi(2) * (-1.0_f64).exp()).cbrt();
+        assert!((some - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn any_zero_part_gives_zero() {
+        assert_eq!(market_quality(&market(0.0, 1000, Some(0)), &now()), 0.0);
+        assert_eq!(market_quality(&market(1e6, 0, Some(0)), &now()), 0.0);
+        assert_eq!(market_quality(&market(1e6, 1000, None), &now()), 0.0);
+        assert_eq!(market_quality(&MarketMetadata::default(), &now()), 0.0);
+    }
+
+    #[test]
+    fn shrinks_thin_markets_toward_the_region() {
+        let constituencies = vec![
+            // Bath and Bristol Central, both in the South West
+            constituency_with_code(
+                "E14001080",
+                &[(PartyName::Labour, 0.2), (PartyName::Conservatives, 0.8)],
+            ),
+            constituency_with_code(
+                "E14001131",
+                &[(PartyName::Labour, 0.8), (PartyName::Conservatives, 0.2)],
+            ),
+        ];
+        let qualities = [1.0, 0.25];
+        let shrunk = shrink_thin_markets(
+            &constituencies,
+            &qualities,
+            0.5,
+            &ThinMarketAdjustment::ShrinkToRegion,
+        );
+        // a deep market is left alone
+        assert_eq!(shrunk[0].parties[0].probability, 0.2);
+        // the prior weights Labour's 0.2 and 0.8 by quality: 0.4 / 1.25, and
+        // the thin market keeps half its own price
+        let labour = shrunk[1].parties[0].probability;
+        assert!((labour - (0.5 * 0.8 + 0.5 * 0.32)).abs() < 1e-12);
+        assert!(labour < 0.8 && labour > 0.32);
+        let total: f64 = shrunk[1]
+            .parties
+            .iter()
+            .map(|party| party.probability)
+            .sum();
+        assert!((total - 1.0).abs() < 1e-12);
+
+        let unchanged = shrink_thin_markets(
+            &constituencies,
+            &qualities,
+            0.5,
+            &ThinMarketAdjustment::None,
+        );
+        assert_eq!(unchanged[1].parties[0].probability, 0.8);
+    }
+
+    #[test]
+    fn shrinking_keeps_the_seat_total() {
+        let constituencies = vec![
+            constituency_with_code(
+                "E14001080",
+                &[(PartyName::Labour, 0.5), (PartyName::Conservatives, 0.5)],
+            ),
+            // an unnormalised market with no trading at all, and a party the
+            // rest of the nation doesn't have
+            constituency_with_code(
+                "E14001131",
+                &[
+                    (PartyName::Labour, 0.6),
+                    (PartyName::Conservatives, 0.4),
+                    (PartyName::Green, 0.2),
+                ],
+            ),
+        ];
+        let shrunk = shrink_thin_markets(
+            &constituencies,
+            &[1.0, 0.0],
+            0.5,
+            &ThinMarketAdjustment::ShrinkToNation,
+        );
+        let probabilities: Vec<f64> = shrunk[1]
+            .parties
+            .iter()
+            .map(|party| party.probability)
+            .collect();
+        // entirely the prior, scaled to the seat's total of 1.2
+        assert!((probabilities[0] - 0.6).abs() < 1e-12);
+        assert!((probabilities[1] - 0.6).abs() < 1e-12);
+        assert_eq!(probabilities[2], 0.0);
+    }
+}
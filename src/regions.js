@@ -0,0 +1,12 @@
+// the summary tables are repeated in divs with a data-region attribute, one
+// for each region. Show only the ones for the region in the selector.
+
+const regionSelector = document.getElementById("region-selector");
+
+regionSelector.addEventListener("change", (e) => {
+  const region = e.target.value;
+  const regionDivisions = document.querySelectorAll("[data-region]");
+  for (const division of regionDivisions) {
+    division.style.display = division.dataset.region === region ? "" : "none";
+  }
+});
@@ -0,0 +1,258 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::constituencies::{find_by_ons_code, match_constituency};
+use crate::parties::PartyAliases;
+use crate::{ConstituencyStatus, DeclaredResult, Party, PartyName, ResultsSummary};
+
+#[derive(Debug)]
+pub enum ResultsError {
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+    Parse {
+        path: String,
+        source: serde_json::Error,
+    },
+    /// A row that couldn't be understood, counting from 1 (for a CSV file,
+    /// the line number).
+    Row {
+        path: String,
+        row: usize,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for ResultsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResultsError::Read { path, source } => write!(f, "could not read {}: {}", path, source),
+            ResultsError::Parse { path, source } => {
+                write!(f, "could not parse {}: {}", path, source)
+            }
+            ResultsError::Row { path, row, message } => {
+                write!(f, "{} row {}: {}", path, row, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResultsError {}
+
+/// The shape of a row in a results file. Either the code or the name has to
+/// be given, the winner can be any alias of a party.
+#[derive(Debug, Deserialize)]
+struct ResultRow {
+    #[serde(default)]
+    ons_code: String,
+    #[serde(default)]
+    constituency: String,
+    winner: String,
+}
+
+/// Read declared results from a `.json` file (a list of
+/// `{"ons_code", "constituency", "winner"}` objects) or a CSV file with the
+/// header `ons_code,constituency,winner`.
+pub fn read_results(
+    path: &str,
+    aliases: &PartyAliases,
+) -> Result<Vec<DeclaredResult>, ResultsError> {
+    let contents = std::fs::read_to_string(path).map_err(|source| ResultsError::Read {
+        path: path.to_string(),
+        source,
+    })?;
+    let rows: Vec<(usize, ResultRow)> = if path.ends_with(".json") {
+        let rows: Vec<ResultRow> =
+            serde_json::from_str(&contents).map_err(|source| ResultsError::Parse {
+                path: path.to_string(),
+                source,
+            })?;
+        rows.into_iter()
+            .enumerate()
+            .map(|(i, row)| (i + 1, row))
+            .collect()
+    } else {
+        parse_csv(path, &contents)?
+    };
+
+    let mut results: Vec<DeclaredResult> = Vec::new();
+    for (line, row) in rows {
+        let row_error = |message: String| ResultsError::Row {
+            path: path.to_string(),
+            row: line,
+            message,
+        };
+        let reference = if row.ons_code.is_empty() {
+            match_constituency(&row.constituency)
+        } else {
+            find_by_ons_code(&row.ons_code)
+        }
+        .ok_or_else(|| {
+            row_error(format!(
+                "no constituency matches `{}`",
+                if row.ons_code.is_empty() {
+                    &row.constituency
+                } else {
+                    &row.ons_code
+                }
+            ))
+        })?;
+        let winner = aliases.parse(&row.winner);
+        if let PartyName::Unparsed(text) = &winner {
+            return Err(row_error(format!("`{}` is not a known party", text)));
+        }
+        if results
+            .iter()
+            .any(|result| result.ons_code == reference.ons_code)
+        {
+            return Err(row_error(format!("{} is declared twice", reference.name)));
+        }
+        results.push(DeclaredResult {
+            ons_code: reference.ons_code.clone(),
+            constituency: reference.name.clone(),
+            winner,
+        });
+    }
+    return Ok(results);
+}
+
+/// Split a CSV line into its cells. A cell can be quoted to hold commas, e.g.
+/// `"Harborough, Oadby and Wigston"`, with `""` for a quote inside it.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut cells = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                cells.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => cells.push(String::new()),
+            c => cells.last_mut().unwrap().push(c),
+        }
+    }
+    return cells
+        .into_iter()
+        .map(|cell| cell.trim().to_string())
+        .collect();
+}
+
+/// A simple CSV reader, enough for a hand-maintained results file: cells can
+/// be quoted, but not span lines, and blank lines are skipped.
+fn parse_csv(path: &str, contents: &str) -> Result<Vec<(usize, ResultRow)>, ResultsError> {
+    let mut lines = contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty());
+    let header: Vec<String> = match lines.next() {
+        Some((_, header)) => split_csv_line(header),
+        None => return Ok(Vec::new()),
+    };
+    let column = |name: &str| header.iter().position(|column| column == name);
+    let winner_column = column("winner").ok_or_else(|| ResultsError::Row {
+        path: path.to_string(),
+        row: 1,
+        message: "there's no `winner` column".to_string(),
+    })?;
+    let ons_code_column = column("ons_code");
+    let constituency_column = column("constituency");
+
+    return Ok(lines
+        .map(|(line, contents)| {
+            let cells = split_csv_line(contents);
+            let cell = |column: Option<usize>| {
+                column
+                    .and_then(|column| cells.get(column))
+                    .cloned()
+                    .unwrap_or_default()
+            };
+            let row = ResultRow {
+                ons_code: cell(ons_code_column),
+                constituency: cell(constituency_column),
+                winner: cell(Some(winner_column)),
+            };
+            return (line, row);
+        })
+        .collect());
+}
+
+/// Fix every declared seat to its winner, so that only the undeclared seats
+/// are left to chance in the simulations.
+pub fn apply_results(
+    constituencies: &[ConstituencyStatus],
+    results: &[DeclaredResult],
+) -> Vec<ConstituencyStatus> {
+    let winners: HashMap<&str, &PartyName> = results
+        .iter()
+        .map(|result| (result.ons_code.as_str(), &result.winner))
+        .collect();
+    return constituencies
+        .iter()
+        .map(
+            |constituency| match winners.get(constituency.ons_code.as_str()) {
+                Some(winner) => ConstituencyStatus {
+                    parties: vec![Party {
+                        name: (*winner).clone(),
+                        probability: 1.0,
+                    }],
+                    ..constituency.clone()
+                },
+                None => constituency.clone(),
+            },
+        )
+        .collect();
+}
+
+pub fn results_summary(results: &[DeclaredResult], total_seats: usize) -> ResultsSummary {
+    let mut declared_by_party: BTreeMap<PartyName, i32> = BTreeMap::new();
+    for result in results {
+        *declared_by_party.entry(result.winner.clone()).or_insert(0) += 1;
+    }
+    let mut declared_by_party: Vec<(PartyName, i32)> = declared_by_party.into_iter().collect();
+    declared_by_party.sort_by(|a, b| (b.1, &b.0).cmp(&(a.1, &a.0)));
+    return ResultsSummary {
+        declared_seats: results.len(),
+        undeclared_seats: total_seats.saturating_sub(results.len()),
+        declared_by_party,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quoted_names_with_commas() {
+        let contents = "constituency,winner\n\
+            \"Harborough, Oadby and Wigston\",Labour\n\
+            \n\
+            \"The \"\"Quoted\"\" Seat\", Conservatives \n";
+        let rows = parse_csv("results.csv", contents).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].0, 2);
+        assert_eq!(rows[0].1.constituency, "Harborough, Oadby and Wigston");
+        assert_eq!(rows[0].1.winner, "Labour");
+        assert_eq!(rows[1].0, 4);
+        assert_eq!(rows[1].1.constituency, "The \"Quoted\" Seat");
+        assert_eq!(rows[1].1.winner, "Conservatives");
+    }
+
+    #[test]
+    fn matches_quoted_names_with_commas() {
+        let path = std::env::temp_dir().join("election_2024_results_test.csv");
+        std::fs::write(
+            &path,
+            "constituency,winner\n\"Harborough, Oadby and Wigston\",Labour\n",
+        )
+        .unwrap();
+        let results = read_results(path.to_str().unwrap(), &PartyAliases::load(None)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].ons_code, "E14001266");
+        assert_eq!(results[0].winner, PartyName::Labour);
+    }
+}
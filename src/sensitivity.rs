@@ -0,0 +1,184 @@
+use std::collections::BTreeSet;
+
+use crate::exact::{add_seat, party_probabilities};
+use crate::simulation::MAJORITY_SEATS;
+use crate::{ConstituencyStatus, PartyEffect, PartyName, PartySensitivity, SeatSensitivity};
+
+/// Smaller changes to a majority probability than this are just rounding.
+const NEGLIGIBLE_EFFECT: f64 = 1e-9;
+
+/// For each seat, the probability that the party wins exactly one short of a
+/// majority in all the other seats, i.e. that the seat decides it. A party's
+/// majority probability goes up by exactly this times any increase in its
+/// probability in the seat, when the seats are independent.
+fn deciding_probabilities(probabilities: &[f64]) -> Vec<f64> {
+    // the distributions of the seats before and after each seat
+    let mut before = vec![vec![1.0]];
+    for probability in probabilities {
+        before.push(add_seat(before.last().unwrap(), *probability));
+    }
+    let mut after = vec![vec![1.0]];
+    for probability in probabilities.iter().rev() {
+        after.push(add_seat(after.last().unwrap(), *probability));
+    }
+    after.reverse();
+
+    let needed = MAJORITY_SEATS - 1;
+    return (0..probabilities.len())
+        .map(|seat| {
+            let (before, after) = (&before[seat], &after[seat + 1]);
+            return (0..=needed.min(before.len() - 1))
+                .filter(|seats| needed - seats < after.len())
+                .map(|seats| before[seats] * after[needed - seats])
+                .sum();
+        })
+        .collect();
+}
+
+/// The effect of moving each party's probability in each seat up by `shift`,
+/// on every party's expected seats and majority probability. The result is in
+/// the same order as `constituencies`, and each seat's parties in its order.
+/// Seats are treated as independent.
+pub fn seat_sensitivities(
+    constituencies: &[ConstituencyStatus],
+    shift: f64,
+) -> Vec<Vec<SeatSensitivity>> {
+    let parties: Vec<PartyName> = constituencies
+        .iter()
+        .flat_map(|constituency| constituency.parties.iter().map(|party| party.name.clone()))
+        .collect::<BTreeSet<PartyName>>()
+        .into_iter()
+        .collect();
+    // indexed by party then seat
+    let deciding: Vec<Vec<f64>> = parties
+        .iter()
+        .map(|name| deciding_probabilities(&party_probabilities(constituencies, name)))
+        .collect();
+
+    return constituencies
+        .iter()
+        .enumerate()
+        .map(|(seat, constituency)| {
+            return constituency
+                .parties
+                .iter()
+                .map(|shifted| {
+                    let new_probability = (shifted.probability + shift).min(1.0);
+                    let rest = 1.0 - shifted.probability;
+                    let mut effects: Vec<PartyEffect> = constituency
+                        .parties
+                        .iter()
+                        .map(|party| {
+                            let change = if party.name == shifted.name {
+                                new_probability - shifted.probability
+                            } else if rest > 0.0 {
+                                party.probability * (1.0 - new_probability) / rest
+                                    - party.probability
+                            } else {
+                                0.0
+                            };
+                            let index = parties.iter().position(|p| *p == party.name).unwrap();
+                            return PartyEffect {
+                                party: party.name.clone(),
+                                expected_seats_change: change,
+                                majority_percentage_change: change * deciding[index][seat],
+                            };
+                        })
+                        .collect();
+                    // the shifted party first
+                    effects.sort_by_key(|effect| effect.party != shifted.name);
+                    return SeatSensitivity {
+                        ons_code: constituency.ons_code.clone(),
+                        constituency: constituency.constituency.clone(),
+                        party: shifted.name.clone(),
+                        probability: shifted.probability,
+                        effects,
+                    };
+                })
+                .collect();
+        })
+        .collect();
+}
+
+/// For each party, the `number_of_seats` seats where a shift towards it moves
+/// its own majority probability the most. When a party's majority probability
+/// can't be moved, e.g. it has no chance of one, its seats are ranked by the
+/// effect on its expected seats instead.
+pub fn most_influential_seats(
+    sensitivities: &[Vec<SeatSensitivity>],
+    number_of_seats: usize,
+) -> Vec<PartySensitivity> {
+    let own_effect = |sensitivity: &SeatSensitivity| {
+        sensitivity
+            .effects
+            .first()
+            .map(|effect| {
+                (
+                    effect.majority_percentage_change,
+                    effect.expected_seats_change,
+                )
+            })
+            .unwrap_or((0.0, 0.0))
+    };
+    let parties: BTreeSet<&PartyName> = sensitivities
+        .iter()
+        .flatten()
+        .map(|sensitivity| &sensitivity.party)
+        .collect();
+    return parties
+        .into_iter()
+        .map(|party| {
+            let mut seats: Vec<SeatSensitivity> = sensitivities
+                .iter()
+                .flatten()
+                .filter(|sensitivity| sensitivity.party == *party)
+                .cloned()
+                .collect();
+            let majority_moves = seats
+                .iter()
+                .any(|sensitivity| own_effect(sensitivity).0 > NEGLIGIBLE_EFFECT);
+            if majority_moves {
+                seats.retain(|sensitivity| own_effect(sensitivity).0 > NEGLIGIBLE_EFFECT);
+                seats.sort_by(|a, b| own_effect(b).0.partial_cmp(&own_effect(a).0).unwrap());
+            } else {
+                seats.sort_by(|a, b| own_effect(b).1.partial_cmp(&own_effect(a).1).unwrap());
+            }
+            seats.truncate(number_of_seats);
+            return PartySensitivity {
+                party: party.clone(),
+                seats,
+            };
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::constituency;
+
+    #[test]
+    fn falls_back_to_expected_seats() {
+        // neither party's majority probability can move: Labour is nowhere
+        // near one and the Conservatives are certain of one
+        let constituencies: Vec<ConstituencyStatus> = (0..650)
+            .map(|seat| {
+                let labour = if seat % 2 == 0 { 0.2 } else { 0.45 };
+                return constituency(&[
+                    (PartyName::Labour, labour),
+                    (PartyName::Conservatives, 1.0 - labour),
+                ]);
+            })
+            .collect();
+        let influential = most_influential_seats(&seat_sensitivities(&constituencies, 0.6), 5);
+        let labour = influential
+            .iter()
+            .find(|sensitivity| sensitivity.party == PartyName::Labour)
+            .unwrap();
+        assert_eq!(labour.seats.len(), 5);
+        // the shift is capped in the seats Labour is more likely to win
+        assert!(labour.seats.iter().all(
+            |seat| seat.probability == 0.2 && seat.effects[0].majority_percentage_change < 1e-9
+        ));
+    }
+}
@@ -0,0 +1,1017 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::constituencies::{areas_of, nation_of};
+use crate::notional::notional_2019_winner;
+use crate::{
+    BlocScenario, ConstituencyStatus, MonteCarloSummary, Nation, PartyName, PivotalSeat, Scenarios,
+    SeatDistribution, SimulationMode,
+};
+
+pub const TOTAL_SEATS: usize = 650;
+pub const MAJORITY_SEATS: usize = 326;
+/// The Speaker doesn't vote, so their seat doesn't count towards a working majority.
+pub const SPEAKER_SEATS: usize = 1;
+
+#[derive(Clone)]
+pub struct SimulationConfig {
+    pub mode: SimulationMode,
+    pub number_of_simulations: usize,
+    pub seed: u64,
+    /// Groups of parties whose combined majority probability we want to know.
+    pub blocs: Vec<Bloc>,
+}
+
+/// A group of parties as configured, e.g. `Labour+LiberalDemocrats`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bloc {
+    pub name: String,
+    pub parties: Vec<PartyName>,
+}
+
+/// Parses party names separated by `+` and blocs separated by `,`, dropping
+/// repeated parties within a bloc and blocs made of the same parties.
+pub fn parse_blocs(blocs: &str) -> Result<Vec<Bloc>, String> {
+    let mut parsed: Vec<Bloc> = Vec::new();
+    for bloc in blocs.split(',').filter(|bloc| !bloc.trim().is_empty()) {
+        let mut parties: Vec<PartyName> = Vec::new();
+        for name in bloc.split('+').map(|name| name.trim()) {
+            let party: PartyName =
+                serde_json::from_value(serde_json::Value::String(name.to_string()))
+                    .map_err(|_| format!("`{}` in bloc `{}` is not a party", name, bloc.trim()))?;
+            if !parties.contains(&party) {
+                parties.push(party);
+            }
+        }
+        let members: BTreeSet<&PartyName> = parties.iter().collect();
+        if parsed
+            .iter()
+            .any(|other| other.parties.iter().collect::<BTreeSet<_>>() == members)
+        {
+            continue;
+        }
+        parsed.push(Bloc {
+            name: bloc.trim().to_string(),
+            parties,
+        });
+    }
+    return Ok(parsed);
+}
+
+/// How many simulations gave a party each possible number of seats (0..=650).
+#[derive(Debug, Clone)]
+pub struct SeatHistogram {
+    pub counts: Vec<u64>,
+}
+
+impl SeatHistogram {
+    pub fn new() -> SeatHistogram {
+        return SeatHistogram {
+            counts: vec![0; TOTAL_SEATS + 1],
+        };
+    }
+
+    pub fn record(&mut self, seats: usize) {
+        self.counts[seats] += 1;
+    }
+
+    pub fn merge(&mut self, other: &SeatHistogram) {
+        for (count, other_count) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other_count;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        return self.counts.iter().sum();
+    }
+
+    /// The seat count at `index` if every simulation's seats were sorted.
+    pub fn nth_smallest(&self, index: u64) -> i32 {
+        let mut cumulative = 0;
+        for (seats, count) in self.counts.iter().enumerate() {
+            cumulative += count;
+            if cumulative > index {
+                return seats as i32;
+            }
+        }
+        return TOTAL_SEATS as i32;
+    }
+
+    /// The seat count below which a proportion `q` of the simulations fall.
+    /// `q = 0` gives the fewest seats simulated and `q = 1` the most.
+    pub fn quantile(&self, q: f64) -> i32 {
+        let total = self.total();
+        let index = (q.clamp(0.0, 1.0) * total as f64) as u64;
+        return self.nth_smallest(index.min(total.saturating_sub(1)));
+    }
+
+    pub fn median(&self) -> i32 {
+        return self.nth_smallest(self.total() / 2);
+    }
+
+    /// The most common seat count, ties resolve to the smallest.
+    pub fn mode(&self) -> i32 {
+        let mut max_count = 0;
+        let mut mode = 0;
+        for (seats, count) in self.counts.iter().enumerate() {
+            if *count > max_count {
+                max_count = *count;
+                mode = seats;
+            }
+        }
+        return mode as i32;
+    }
+
+    pub fn mean(&self) -> f64 {
+        let total: u64 = self
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(seats, count)| seats as u64 * count)
+            .sum();
+        return total as f64 / self.total().max(1) as f64;
+    }
+
+    pub fn proportion_at_least(&self, seats: usize) -> f64 {
+        let count: u64 = self.counts[seats..].iter().sum();
+        return count as f64 / self.total() as f64;
+    }
+
+    pub fn to_distribution(&self) -> SeatDistribution {
+        let first = self.counts.iter().position(|count| *count > 0).unwrap_or(0);
+        let last = self
+            .counts
+            .iter()
+            .rposition(|count| *count > 0)
+            .unwrap_or(0);
+        return SeatDistribution {
+            min_seats: first as i32,
+            counts: self.counts[first..=last].to_vec(),
+        };
+    }
+}
+
+impl Default for SeatHistogram {
+    fn default() -> Self {
+        return SeatHistogram::new();
+    }
+}
+
+/// How many simulated parliaments fell into each scenario.
+#[derive(Clone)]
+struct ScenarioCounts {
+    hung_parliament: u64,
+    effective_hung_parliament: u64,
+    largest_party: Vec<u64>,
+    tied_largest_party: u64,
+    effective_majority: Vec<u64>,
+    bloc_majority: Vec<u64>,
+    bloc_effective_majority: Vec<u64>,
+}
+
+impl ScenarioCounts {
+    fn new(number_of_parties: usize, number_of_blocs: usize) -> ScenarioCounts {
+        return ScenarioCounts {
+            hung_parliament: 0,
+            effective_hung_parliament: 0,
+            largest_party: vec![0; number_of_parties],
+            tied_largest_party: 0,
+            effective_majority: vec![0; number_of_parties],
+            bloc_majority: vec![0; number_of_blocs],
+            bloc_effective_majority: vec![0; number_of_blocs],
+        };
+    }
+
+    fn merge(&mut self, other: &ScenarioCounts) {
+        self.hung_parliament += other.hung_parliament;
+        self.effective_hung_parliament += other.effective_hung_parliament;
+        self.tied_largest_party += other.tied_largest_party;
+        let add = |counts: &mut Vec<u64>, other_counts: &Vec<u64>| {
+            for (count, other_count) in counts.iter_mut().zip(other_counts) {
+                *count += other_count;
+            }
+        };
+        add(&mut self.largest_party, &other.largest_party);
+        add(&mut self.effective_majority, &other.effective_majority);
+        add(&mut self.bloc_majority, &other.bloc_majority);
+        add(
+            &mut self.bloc_effective_majority,
+            &other.bloc_effective_majority,
+        );
+    }
+}
+
+/// Everything we keep from the simulated elections, accumulated as they run.
+pub struct SimulationResults {
+    pub parties: Vec<PartyName>,
+    pub histograms: Vec<SeatHistogram>,
+    /// The English regions, the nations, and England as a whole.
+    pub areas: Vec<String>,
+    /// The number of seats in each area.
+    pub area_seats: Vec<usize>,
+    /// Indexed by area then party, from the same draws as `histograms`.
+    pub area_histograms: Vec<Vec<SeatHistogram>>,
+    /// How many seats each party notionally won in 2019, counting only the
+    /// seats with a notional winner who is standing.
+    pub notional_seats: Vec<usize>,
+    /// The seats each party won from another party's notional 2019 seats.
+    pub gains_histograms: Vec<SeatHistogram>,
+    /// The notional 2019 seats each party lost to another party.
+    pub losses_histograms: Vec<SeatHistogram>,
+    /// The seats each party won among those counted in `notional_seats`, so
+    /// the net change is this less the notional seats.
+    pub covered_histograms: Vec<SeatHistogram>,
+    /// Indexed by the notional 2019 winner then the simulated winner, summed
+    /// over every simulation.
+    pub transitions: Vec<Vec<u64>>,
+    blocs: Vec<Bloc>,
+    /// The members of each bloc who are standing somewhere, as party indices.
+    bloc_members: Vec<Vec<usize>>,
+    scenarios: ScenarioCounts,
+    /// For each party, every seat from its most to least likely win.
+    seat_orders: Vec<Vec<usize>>,
+    /// Indexed by seat then party.
+    seat_wins: Vec<Vec<u64>>,
+    /// As `seat_wins`, counting only the simulations where the party won a
+    /// majority.
+    seat_wins_with_majority: Vec<Vec<u64>>,
+    /// How often each seat took each party over the majority line.
+    tipping_points: Vec<Vec<u64>>,
+}
+
+/// The tallies from a single simulated election.
+struct SimulatedElection {
+    seat_counts: Vec<usize>,
+    area_seat_counts: Vec<Vec<usize>>,
+    gains: Vec<usize>,
+    losses: Vec<usize>,
+    covered_seat_counts: Vec<usize>,
+    /// The party winning each seat.
+    winners: Vec<usize>,
+    /// The (notional 2019 winner, simulated winner) of every seat changing hands.
+    transitions: Vec<(usize, usize)>,
+}
+
+impl SimulatedElection {
+    fn new(number_of_parties: usize, number_of_areas: usize) -> SimulatedElection {
+        return SimulatedElection {
+            seat_counts: vec![0; number_of_parties],
+            area_seat_counts: vec![vec![0; number_of_parties]; number_of_areas],
+            gains: vec![0; number_of_parties],
+            losses: vec![0; number_of_parties],
+            covered_seat_counts: vec![0; number_of_parties],
+            winners: Vec::new(),
+            transitions: Vec::new(),
+        };
+    }
+}
+
+impl SimulationResults {
+    fn new(
+        parties: &[PartyName],
+        areas: &[String],
+        area_seats: &[usize],
+        notional_seats: &[usize],
+        blocs: &[Bloc],
+        seat_orders: &[Vec<usize>],
+    ) -> SimulationResults {
+        let number_of_seats = seat_orders.first().map(|order| order.len()).unwrap_or(0);
+        return SimulationResults {
+            parties: parties.to_vec(),
+            histograms: vec![SeatHistogram::new(); parties.len()],
+            areas: areas.to_vec(),
+            area_seats: area_seats.to_vec(),
+            area_histograms: vec![vec![SeatHistogram::new(); parties.len()]; areas.len()],
+            notional_seats: notional_seats.to_vec(),
+            gains_histograms: vec![SeatHistogram::new(); parties.len()],
+            losses_histograms: vec![SeatHistogram::new(); parties.len()],
+            covered_histograms: vec![SeatHistogram::new(); parties.len()],
+            transitions: vec![vec![0; parties.len()]; parties.len()],
+            blocs: blocs.to_vec(),
+            // parties in a bloc who aren't standing anywhere can't add any seats
+            bloc_members: blocs
+                .iter()
+                .map(|bloc| {
+                    bloc.parties
+                        .iter()
+                        .filter_map(|name| parties.iter().position(|party| party == name))
+                        .collect()
+                })
+                .collect(),
+            scenarios: ScenarioCounts::new(parties.len(), blocs.len()),
+            seat_orders: seat_orders.to_vec(),
+            seat_wins: vec![vec![0; parties.len()]; number_of_seats],
+            seat_wins_with_majority: vec![vec![0; parties.len()]; number_of_seats],
+            tipping_points: vec![vec![0; parties.len()]; number_of_seats],
+        };
+    }
+
+    fn record(&mut self, election: &SimulatedElection) {
+        let seat_counts = &election.seat_counts;
+        for (histogram, seats) in self.histograms.iter_mut().zip(seat_counts) {
+            histogram.record(*seats);
+        }
+        for (histograms, seat_counts) in self
+            .area_histograms
+            .iter_mut()
+            .zip(&election.area_seat_counts)
+        {
+            for (histogram, seats) in histograms.iter_mut().zip(seat_counts) {
+                histogram.record(*seats);
+            }
+        }
+        for (histogram, seats) in self.gains_histograms.iter_mut().zip(&election.gains) {
+            histogram.record(*seats);
+        }
+        for (histogram, seats) in self.losses_histograms.iter_mut().zip(&election.losses) {
+            histogram.record(*seats);
+        }
+        for (histogram, seats) in self
+            .covered_histograms
+            .iter_mut()
+            .zip(&election.covered_seat_counts)
+        {
+            histogram.record(*seats);
+        }
+        for (from, to) in &election.transitions {
+            self.transitions[*from][*to] += 1;
+        }
+        for (seat, winner) in election.winners.iter().enumerate() {
+            self.seat_wins[seat][*winner] += 1;
+        }
+        // at most one party can have a majority, walk its seats from the
+        // safest to find the one which took it over the line
+        if let Some(majority_party) = seat_counts
+            .iter()
+            .position(|seats| *seats >= MAJORITY_SEATS)
+        {
+            let mut seats_won = 0;
+            for seat in &self.seat_orders[majority_party] {
+                if election.winners[*seat] != majority_party {
+                    continue;
+                }
+                self.seat_wins_with_majority[*seat][majority_party] += 1;
+                seats_won += 1;
+                if seats_won == MAJORITY_SEATS {
+                    self.tipping_points[*seat][majority_party] += 1;
+                }
+            }
+        }
+
+        // sinn féin don't take their seats, so they lower the bar for a working majority
+        let sinn_fein_seats = self
+            .parties
+            .iter()
+            .position(|party| *party == PartyName::SinnFein)
+            .map(|index| seat_counts[index])
+            .unwrap_or(0);
+        let voting_seats = TOTAL_SEATS - SPEAKER_SEATS - sinn_fein_seats.min(TOTAL_SEATS - 1);
+        let has_majority = |seats: usize| seats >= MAJORITY_SEATS;
+        let has_effective_majority = |seats: usize| 2 * seats > voting_seats;
+
+        let scenarios = &mut self.scenarios;
+        if !seat_counts.iter().any(|seats| has_majority(*seats)) {
+            scenarios.hung_parliament += 1;
+        }
+        if !seat_counts
+            .iter()
+            .any(|seats| has_effective_majority(*seats))
+        {
+            scenarios.effective_hung_parliament += 1;
+        }
+        for (party, seats) in seat_counts.iter().enumerate() {
+            if has_effective_majority(*seats) {
+                scenarios.effective_majority[party] += 1;
+            }
+        }
+
+        let most_seats = seat_counts.iter().max().cloned().unwrap_or(0);
+        let largest_parties: Vec<usize> = (0..seat_counts.len())
+            .filter(|party| seat_counts[*party] == most_seats)
+            .collect();
+        if largest_parties.len() == 1 {
+            scenarios.largest_party[largest_parties[0]] += 1;
+        } else {
+            scenarios.tied_largest_party += 1;
+        }
+
+        for (bloc_index, bloc) in self.bloc_members.iter().enumerate() {
+            let bloc_seats: usize = bloc.iter().map(|party| seat_counts[*party]).sum();
+            if has_majority(bloc_seats) {
+                scenarios.bloc_majority[bloc_index] += 1;
+            }
+            if has_effective_majority(bloc_seats) {
+                scenarios.bloc_effective_majority[bloc_index] += 1;
+            }
+        }
+    }
+
+    fn merge(mut self, other: SimulationResults) -> SimulationResults {
+        for (histogram, other_histogram) in self.histograms.iter_mut().zip(&other.histograms) {
+            histogram.merge(other_histogram);
+        }
+        for (histograms, other_histograms) in
+            self.area_histograms.iter_mut().zip(&other.area_histograms)
+        {
+            for (histogram, other_histogram) in histograms.iter_mut().zip(other_histograms) {
+                histogram.merge(other_histogram);
+            }
+        }
+        for (histogram, other_histogram) in self
+            .gains_histograms
+            .iter_mut()
+            .zip(&other.gains_histograms)
+        {
+            histogram.merge(other_histogram);
+        }
+        for (histogram, other_histogram) in self
+            .losses_histograms
+            .iter_mut()
+            .zip(&other.losses_histograms)
+        {
+            histogram.merge(other_histogram);
+        }
+        for (histogram, other_histogram) in self
+            .covered_histograms
+            .iter_mut()
+            .zip(&other.covered_histograms)
+        {
+            histogram.merge(other_histogram);
+        }
+        let add = |counts: &mut Vec<Vec<u64>>, other_counts: &Vec<Vec<u64>>| {
+            for (counts, other_counts) in counts.iter_mut().zip(other_counts) {
+                for (count, other_count) in counts.iter_mut().zip(other_counts) {
+                    *count += other_count;
+                }
+            }
+        };
+        add(&mut self.transitions, &other.transitions);
+        add(&mut self.seat_wins, &other.seat_wins);
+        add(
+            &mut self.seat_wins_with_majority,
+            &other.seat_wins_with_majority,
+        );
+        add(&mut self.tipping_points, &other.tipping_points);
+        self.scenarios.merge(&other.scenarios);
+        return self;
+    }
+
+    pub fn number_of_simulations(&self) -> u64 {
+        return self
+            .histograms
+            .first()
+            .map(|histogram| histogram.total())
+            .unwrap_or(0);
+    }
+
+    pub fn scenarios(&self) -> Scenarios {
+        let total = self.number_of_simulations().max(1) as f64;
+        let counts = &self.scenarios;
+        let per_party = |party_counts: &Vec<u64>| {
+            let mut percentages: Vec<(PartyName, f64)> = self
+                .parties
+                .iter()
+                .zip(party_counts)
+                .filter(|(_, count)| **count > 0)
+                .map(|(party, count)| (party.clone(), *count as f64 / total))
+                .collect();
+            percentages.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+            return percentages;
+        };
+        return Scenarios {
+            hung_parliament_percentage: counts.hung_parliament as f64 / total,
+            effective_hung_parliament_percentage: counts.effective_hung_parliament as f64 / total,
+            largest_party: per_party(&counts.largest_party),
+            tied_largest_party_percentage: counts.tied_largest_party as f64 / total,
+            effective_majority: per_party(&counts.effective_majority),
+            blocs: self
+                .blocs
+                .iter()
+                .enumerate()
+                .map(|(bloc_index, bloc)| BlocScenario {
+                    name: bloc.name.clone(),
+                    parties: bloc.parties.clone(),
+                    not_standing: bloc
+                        .parties
+                        .iter()
+                        .filter(|party| !self.parties.contains(party))
+                        .cloned()
+                        .collect(),
+                    majority_percentage: counts.bloc_majority[bloc_index] as f64 / total,
+                    effective_majority_percentage: counts.bloc_effective_majority[bloc_index]
+                        as f64
+                        / total,
+                })
+                .collect(),
+        };
+    }
+
+    pub fn summaries(&self) -> Vec<MonteCarloSummary> {
+        return summarise(&self.parties, &self.histograms, MAJORITY_SEATS);
+    }
+
+    /// The seats which most often decide whether a party wins a majority,
+    /// with `constituencies` in the same order as given to `run_monte_carlo`.
+    /// Each seat is reported for the party it most often tipped over the line,
+    /// along with how well winning it tracks that party winning a majority.
+    pub fn pivotal_seats(&self, constituencies: &[ConstituencyStatus]) -> Vec<PivotalSeat> {
+        let total = self.number_of_simulations();
+        let mut pivotal_seats: Vec<PivotalSeat> = constituencies
+            .iter()
+            .enumerate()
+            .filter_map(|(seat, constituency)| {
+                let (party, tipping_points) = self.tipping_points[seat]
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(_, count)| **count)?;
+                if *tipping_points == 0 {
+                    return None;
+                }
+                let majorities = self.histograms[party].counts[MAJORITY_SEATS..].iter().sum();
+                return Some(PivotalSeat {
+                    ons_code: constituency.ons_code.clone(),
+                    constituency: constituency.constituency.clone(),
+                    party: self.parties[party].clone(),
+                    tipping_point_probability: *tipping_points as f64 / total as f64,
+                    majority_correlation: phi_coefficient(
+                        total,
+                        self.seat_wins[seat][party],
+                        majorities,
+                        self.seat_wins_with_majority[seat][party],
+                    ),
+                });
+            })
+            .collect();
+        pivotal_seats.sort_by(|a, b| {
+            b.tipping_point_probability
+                .partial_cmp(&a.tipping_point_probability)
+                .unwrap()
+        });
+        return pivotal_seats;
+    }
+
+    /// The summaries for each area, in the same order as `areas`. Here
+    /// `majority_percentage` is how often a party won more than half of the
+    /// area's seats.
+    pub fn area_summaries(&self) -> Vec<Vec<MonteCarloSummary>> {
+        return self
+            .area_histograms
+            .iter()
+            .zip(&self.area_seats)
+            .map(|(histograms, seats)| summarise(&self.parties, histograms, seats / 2 + 1))
+            .collect();
+    }
+}
+
+/// The correlation between two yes/no outcomes over `total` trials, from how
+/// often each happened and how often both did. Zero if either never varies.
+fn phi_coefficient(total: u64, first: u64, second: u64, both: u64) -> f64 {
+    let (total, first, second, both) = (total as f64, first as f64, second as f64, both as f64);
+    let denominator = (first * (total - first) * second * (total - second)).sqrt();
+    if denominator == 0.0 {
+        return 0.0;
+    }
+    return (total * both - first * second) / denominator;
+}
+
+fn summarise(
+    parties: &[PartyName],
+    histograms: &[SeatHistogram],
+    majority_seats: usize,
+) -> Vec<MonteCarloSummary> {
+    let mut summaries: Vec<MonteCarloSummary> = parties
+        .iter()
+        .zip(histograms)
+        // only include parties which won a seat in at least one simulation
+        .filter(|(_, histogram)| histogram.counts[0] < histogram.total())
+        .map(|(party, histogram)| MonteCarloSummary {
+            party: party.clone(),
+            seats: histogram.clone(),
+            mode: histogram.mode(),
+            median: histogram.median(),
+            lower_5th: histogram.quantile(0.05),
+            upper_95th: histogram.quantile(0.95),
+            majority_percentage: histogram.proportion_at_least(majority_seats),
+        })
+        .collect();
+
+    // sort by the median
+    summaries.sort_by_key(|summary| std::cmp::Reverse(summary.median));
+
+    return summaries;
+}
+
+/// A constituency's market, with parties replaced by their index in the simulation.
+struct SimulatedConstituency {
+    nation: Nation,
+    /// The indexes of the areas the seat counts towards.
+    areas: Vec<usize>,
+    notional_winner: Option<usize>,
+    parties: Vec<(usize, f64)>,
+}
+
+/// The rng for one simulation. The seed and the simulation number make up the
+/// key, so every (seed, simulation) pair gets its own stream; adding them
+/// instead would have seeds 7 and 8 sharing all but one simulation.
+fn simulation_rng(seed: u64, simulation: usize) -> StdRng {
+    let mut key = [0u8; 32];
+    key[..8].copy_from_slice(&seed.to_le_bytes());
+    key[8..16].copy_from_slice(&(simulation as u64).to_le_bytes());
+    return StdRng::from_seed(key);
+}
+
+pub fn run_monte_carlo(
+    constituencies: &[ConstituencyStatus],
+    config: &SimulationConfig,
+) -> SimulationResults {
+    // run a monte carlo simulation
+    // for each constituency, pick a party based on the probabilities
+    // and increment the count for that party
+    // do this many times, accumulating the seat counts into a histogram per party
+    let parties: Vec<PartyName> = constituencies
+        .iter()
+        .flat_map(|constituency| constituency.parties.iter().map(|party| party.name.clone()))
+        .collect::<BTreeSet<PartyName>>()
+        .into_iter()
+        .collect();
+    let areas: Vec<String> = constituencies
+        .iter()
+        .flat_map(areas_of)
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .collect();
+    let mut area_seats = vec![0; areas.len()];
+    let simulated_constituencies: Vec<SimulatedConstituency> = constituencies
+        .iter()
+        .map(|constituency| SimulatedConstituency {
+            nation: nation_of(constituency),
+            areas: areas_of(constituency)
+                .iter()
+                .map(|area| {
+                    let index = areas.iter().position(|a| a == area).unwrap();
+                    area_seats[index] += 1;
+                    return index;
+                })
+                .collect(),
+            // a notional winner who isn't standing anywhere can't be tracked
+            notional_winner: notional_2019_winner(&constituency.ons_code)
+                .and_then(|winner| parties.iter().position(|party| party == winner)),
+            parties: constituency
+                .parties
+                .iter()
+                .map(|party| {
+                    let index = parties.iter().position(|p| *p == party.name).unwrap();
+                    return (index, party.probability);
+                })
+                .collect(),
+        })
+        .collect();
+    let seat_orders: Vec<Vec<usize>> = (0..parties.len())
+        .map(|party| {
+            let probability = |seat: usize| {
+                simulated_constituencies[seat]
+                    .parties
+                    .iter()
+                    .find(|(index, _)| *index == party)
+                    .map(|(_, probability)| *probability)
+                    .unwrap_or(0.0)
+            };
+            let mut order: Vec<usize> = (0..simulated_constituencies.len()).collect();
+            // a stable sort, so ties stay in the order given
+            order.sort_by(|a, b| probability(*b).partial_cmp(&probability(*a)).unwrap());
+            return order;
+        })
+        .collect();
+    let mut notional_seats = vec![0; parties.len()];
+    for constituency in &simulated_constituencies {
+        if let Some(notional_winner) = constituency.notional_winner {
+            notional_seats[notional_winner] += 1;
+        }
+    }
+
+    // each simulation gets its own rng derived from the seed, so the results
+    // don't depend on how rayon schedules the work
+    return (0..config.number_of_simulations)
+        .into_par_iter()
+        .fold(
+            || {
+                SimulationResults::new(
+                    &parties,
+                    &areas,
+                    &area_seats,
+                    &notional_seats,
+                    &config.blocs,
+                    &seat_orders,
+                )
+            },
+            |mut results, simulation| {
+                let mut rng = simulation_rng(config.seed, simulation);
+                let shocks = draw_shocks(&mut rng, parties.len(), &config.mode);
+                let mut election = SimulatedElection::new(parties.len(), areas.len());
+                for constituency in &simulated_constituencies {
+                    // randomly pick a party based on the (shocked) probabilities
+                    let (winner, _) = constituency
+                        .parties
+                        .choose_weighted(&mut rng, |(party, probability)| {
+                            shocks.apply(*party, *probability, constituency.nation)
+                        })
+                        .unwrap();
+                    election.seat_counts[*winner] += 1;
+                    election.winners.push(*winner);
+                    for area in &constituency.areas {
+                        election.area_seat_counts[*area][*winner] += 1;
+                    }
+                    if let Some(notional_winner) = constituency.notional_winner {
+                        election.covered_seat_counts[*winner] += 1;
+                        if notional_winner != *winner {
+                            election.gains[*winner] += 1;
+                            election.losses[notional_winner] += 1;
+                            election.transitions.push((notional_winner, *winner));
+                        }
+                    }
+                }
+                results.record(&election);
+                return results;
+            },
+        )
+        .reduce(
+            || {
+                SimulationResults::new(
+                    &parties,
+                    &areas,
+                    &area_seats,
+                    &notional_seats,
+                    &config.blocs,
+                    &seat_orders,
+                )
+            },
+            SimulationResults::merge,
+        );
+}
+
+/// The log-odds shocks shared by every seat in a single simulated election,
+/// indexed by party.
+struct Shocks {
+    national: Vec<f64>,
+    nation: HashMap<Nation, Vec<f64>>,
+}
+
+impl Shocks {
+    fn apply(&self, party: usize, probability: f64, nation: Nation) -> f64 {
+        let national = self.national[party];
+        let nation = self
+            .nation
+            .get(&nation)
+            .map(|shocks| shocks[party])
+            .unwrap_or(0.0);
+        // shifting every party's log-odds and letting choose_weighted renormalise
+        // is the same as scaling each probability by exp(shock)
+        return probability * (national + nation).exp();
+    }
+}
+
+fn draw_shocks<R: Rng>(rng: &mut R, number_of_parties: usize, mode: &SimulationMode) -> Shocks {
+    let mut shocks = Shocks {
+        national: vec![0.0; number_of_parties],
+        nation: HashMap::new(),
+    };
+    if let SimulationMode::Correlated {
+        national_shock,
+        nation_shock,
+    } = mode
+    {
+        for shock in shocks.national.iter_mut() {
+            *shock = standard_normal(rng) * national_shock;
+        }
+        for nation in [Nation::Scotland, Nation::Wales, Nation::NorthernIreland] {
+            let nation_shocks = (0..number_of_parties)
+                .map(|_| standard_normal(rng) * nation_shock)
+                .collect();
+            shocks.nation.insert(nation, nation_shocks);
+        }
+    }
+    return shocks;
+}
+
+fn standard_normal<R: Rng>(rng: &mut R) -> f64 {
+    // Box-Muller transform
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen::<f64>();
+    return (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{to_simple_summary, MarketMetadata, MonteCarloSummarySimple, Party};
+
+    fn constituencies() -> Vec<ConstituencyStatus> {
+        return (0..650)
+            .map(|seat| {
+                let labour = 0.2 + 0.6 * (seat % 5) as f64 / 4.0;
+                return ConstituencyStatus {
+                    ons_code: "".to_string(),
+                    constituency: "".to_string(),
+                    parties: vec![
+                        Party {
+                            name: PartyName::Labour,
+                            probability: labour,
+                        },
+                        Party {
+                            name: PartyName::Conservatives,
+                            probability: 1.0 - labour,
+                        },
+                    ],
+                    manifold_url: "".to_string(),
+                    market: MarketMetadata::default(),
+                };
+            })
+            .collect();
+    }
+
+    /// The summaries and scenarios as JSON, to compare runs byte for byte.
+    fn run(seed: u64, threads: usize) -> String {
+        let config = SimulationConfig {
+            mode: SimulationMode::Correlated {
+                national_shock: 0.05,
+                nation_shock: 0.03,
+            },
+            number_of_simulations: 500,
+            seed,
+            blocs: Vec::new(),
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        let results = pool.install(|| run_monte_carlo(&constituencies(), &config));
+        let summaries: Vec<MonteCarloSummarySimple> = results
+            .summaries()
+            .iter()
+            .map(|summary| to_simple_summary(summary, &[5.0, 50.0, 95.0]))
+            .collect();
+        return serde_json::to_string(&(summaries, results.scenarios())).unwrap();
+    }
+
+    #[test]
+    fn phi_coefficient_by_hand() {
+        // always together, never together, and independent
+        assert_eq!(phi_coefficient(4, 2, 2, 2), 1.0);
+        assert_eq!(phi_coefficient(4, 2, 2, 0), -1.0);
+        assert_eq!(phi_coefficient(4, 2, 2, 1), 0.0);
+        // (4 * 1 - 2 * 1) / sqrt(2 * 2 * 1 * 3)
+        assert!((phi_coefficient(4, 2, 1, 1) - 2.0 / 12.0_f64.sqrt()).abs() < 1e-12);
+        // an outcome that never varies has no correlation
+        assert_eq!(phi_coefficient(4, 4, 2, 2), 0.0);
+        assert_eq!(phi_coefficient(4, 0, 2, 0), 0.0);
+    }
+
+    #[test]
+    fn one_seat_decides_the_majority() {
+        // Labour are certain of 325 seats and the Conservatives of 324, so
+        // Labour win a majority exactly when they win the last seat
+        let mut constituencies: Vec<ConstituencyStatus> = (0..649)
+            .map(|seat| {
+                let party = if seat < 325 {
+                    PartyName::Labour
+                } else {
+                    PartyName::Conservatives
+                };
+                return crate::fixtures::constituency_with_code(
+                    &format!("S{}", seat),
+                    &[(party, 1.0)],
+                );
+            })
+            .collect();
+        constituencies.insert(
+            300,
+            crate::fixtures::constituency_with_code(
+                "DECIDER",
+                &[(PartyName::Labour, 0.5), (PartyName::Conservatives, 0.5)],
+            ),
+        );
+        let config = SimulationConfig {
+            mode: SimulationMode::Independent,
+            number_of_simulations: 1000,
+            seed: 7,
+            blocs: Vec::new(),
+        };
+        let results = run_monte_carlo(&constituencies, &config);
+        let labour_majority = results
+            .summaries()
+            .iter()
+            .find(|summary| summary.party == PartyName::Labour)
+            .unwrap()
+            .majority_percentage;
+        assert!(labour_majority > 0.4 && labour_majority < 0.6);
+
+        let pivotal_seats = results.pivotal_seats(&constituencies);
+        assert_eq!(pivotal_seats.len(), 1);
+        let decider = &pivotal_seats[0];
+        assert_eq!(decider.ons_code, "DECIDER");
+        assert_eq!(decider.party, PartyName::Labour);
+        assert_eq!(decider.tipping_point_probability, labour_majority);
+        assert!((decider.majority_correlation - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn blocs_are_deduplicated() {
+        let blocs =
+            parse_blocs("Labour+LiberalDemocrats, LiberalDemocrats + Labour + Labour,Labour+SNP")
+                .unwrap();
+        assert_eq!(
+            blocs,
+            vec![
+                Bloc {
+                    name: "Labour+LiberalDemocrats".to_string(),
+                    parties: vec![PartyName::Labour, PartyName::LiberalDemocrats],
+                },
+                Bloc {
+                    name: "Labour+SNP".to_string(),
+                    parties: vec![PartyName::Labour, PartyName::SNP],
+                },
+            ]
+        );
+        assert!(parse_blocs("Labour+Nobody").is_err());
+    }
+
+    #[test]
+    fn blocs_keep_members_who_are_not_standing() {
+        let config = SimulationConfig {
+            mode: SimulationMode::Independent,
+            number_of_simulations: 200,
+            seed: 1,
+            blocs: parse_blocs("Labour,Labour+SNP,Conservatives+Labour").unwrap(),
+        };
+        let scenarios = run_monte_carlo(&constituencies(), &config).scenarios();
+        let names: Vec<&str> = scenarios
+            .blocs
+            .iter()
+            .map(|bloc| bloc.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Labour", "Labour+SNP", "Conservatives+Labour"]);
+        assert_eq!(
+            scenarios.blocs[1].parties,
+            vec![PartyName::Labour, PartyName::SNP]
+        );
+        assert_eq!(scenarios.blocs[0].not_standing, Vec::<PartyName>::new());
+        assert_eq!(scenarios.blocs[1].not_standing, vec![PartyName::SNP]);
+        // the SNP add no seats, and both parties together hold every seat
+        assert_eq!(
+            scenarios.blocs[1].majority_percentage,
+            scenarios.blocs[0].majority_percentage
+        );
+        assert_eq!(scenarios.blocs[2].majority_percentage, 1.0);
+        let labour_majority = scenarios
+            .effective_majority
+            .iter()
+            .find(|(party, _)| *party == PartyName::Labour)
+            .map(|(_, percentage)| *percentage)
+            .unwrap_or(0.0);
+        assert_eq!(
+            scenarios.blocs[0].effective_majority_percentage,
+            labour_majority
+        );
+    }
+
+    #[test]
+    fn quantiles_at_the_edges() {
+        let mut histogram = SeatHistogram::new();
+        for seats in [3, 5, 5, 9] {
+            histogram.record(seats);
+        }
+        assert_eq!(histogram.quantile(0.0), 3);
+        assert_eq!(histogram.quantile(0.5), 5);
+        assert_eq!(histogram.quantile(1.0), 9);
+        // the same convention as the exact distributions
+        let exact = crate::exact::ExactDistribution::new(&[1.0, 0.5, 0.0]);
+        let mut histogram = SeatHistogram::new();
+        histogram.record(1);
+        histogram.record(2);
+        for q in [0.0, 0.5, 1.0] {
+            assert_eq!(histogram.quantile(q), exact.quantile(q));
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_identical_results() {
+        let first = run(7, 1);
+        assert_eq!(first, run(7, 1));
+        assert_eq!(first, run(7, 3));
+        assert_ne!(first, run(8, 1));
+    }
+
+    #[test]
+    fn neighbouring_seeds_share_no_streams() {
+        let draws = |seed: u64| -> Vec<u64> {
+            return (0..100)
+                .map(|simulation| simulation_rng(seed, simulation).gen())
+                .collect();
+        };
+        let (seven, eight) = (draws(7), draws(8));
+        assert!(seven.iter().all(|draw| !eight.contains(draw)));
+    }
+}
@@ -5,6 +5,10 @@
 // - conservative-probability
 // - other-probability
 // - favourite-lead
+// - overround
+// - volume
+// - unique-bettors
+// - market-quality
 // We want to sort the consituencies by the key passed in the function
 
 const sorters = document.querySelectorAll("[data-sort]");
@@ -0,0 +1,212 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    ArchivedAggregate, ConstituencyTimePoint, ConstituencyTimeSeries, Party, PartyName,
+    PartyTimePoint, PartyTimeSeries, Status, TimeSeries,
+};
+
+/// How the favourites and simulations have moved over the archived runs,
+/// with `statuses` oldest first.
+pub fn make_time_series(statuses: &[Status], aggregates: &[ArchivedAggregate]) -> TimeSeries {
+    return TimeSeries {
+        constituencies: make_constituency_time_series(statuses),
+        parties: make_party_time_series(statuses, aggregates),
+    };
+}
+
+fn favourite(parties: &[Party]) -> Option<&Party> {
+    return parties
+        .iter()
+        .max_by(|a, b| a.probability.partial_cmp(&b.probability).unwrap());
+}
+
+/// Keyed by ONS code, as a market's display name can change between runs.
+fn make_constituency_time_series(statuses: &[Status]) -> Vec<ConstituencyTimeSeries> {
+    let mut series: BTreeMap<String, ConstituencyTimeSeries> = BTreeMap::new();
+    for status in statuses {
+        for constituency in &status.constituencies {
+            // a market which couldn't be matched to a seat has no code
+            if constituency.ons_code.is_empty() {
+                continue;
+            }
+            let favourite = match favourite(&constituency.parties) {
+                Some(favourite) => favourite,
+                None => continue,
+            };
+            let entry = series
+                .entry(constituency.ons_code.clone())
+                .or_insert_with(|| ConstituencyTimeSeries {
+                    ons_code: constituency.ons_code.clone(),
+                    constituency: String::new(),
+                    points: Vec::new(),
+                });
+            entry.constituency = constituency.constituency.clone();
+            entry.points.push(ConstituencyTimePoint {
+                fetched_at: status.fetched_at,
+                favourite: favourite.name.clone(),
+                favourite_probability: favourite.probability,
+            });
+        }
+    }
+    return series.into_values().collect();
+}
+
+fn make_party_time_series(
+    statuses: &[Status],
+    aggregates: &[ArchivedAggregate],
+) -> Vec<PartyTimeSeries> {
+    // every party that was ever a favourite or in a simulation summary
+    let mut parties: BTreeSet<PartyName> = BTreeSet::new();
+    for status in statuses {
+        for constituency in &status.constituencies {
+            if let Some(favourite) = favourite(&constituency.parties) {
+                parties.insert(favourite.name.clone());
+            }
+        }
+    }
+    for aggregate in aggregates {
+        for summary in &aggregate.monte_carlo_summary {
+            parties.insert(summary.party.clone());
+        }
+    }
+
+    return parties
+        .into_iter()
+        .map(|party| {
+            let points = statuses
+                .iter()
+                .map(|status| {
+                    let favourite_seats = status
+                        .constituencies
+                        .iter()
+                        .filter(|constituency| {
+                            favourite(&constituency.parties)
+                                .map(|favourite| favourite.name == party)
+                                .unwrap_or(false)
+                        })
+                        .count() as i32;
+                    let aggregate = aggregates
+                        .iter()
+                        .find(|aggregate| aggregate.fetched_at == status.fetched_at);
+                    // a party missing from an aggregate's summary never won a seat in it
+                    let summary = aggregate.map(|aggregate| {
+                        aggregate
+                            .monte_carlo_summary
+                            .iter()
+                            .find(|summary| summary.party == party)
+                    });
+                    return PartyTimePoint {
+                        fetched_at: status.fetched_at,
+                        favourite_seats,
+                        median: summary.map(|summary| summary.map(|s| s.median).unwrap_or(0)),
+                        majority_percentage: summary
+                            .map(|summary| summary.map(|s| s.majority_percentage).unwrap_or(0.0)),
+                    };
+                })
+                .collect();
+            return PartyTimeSeries { party, points };
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::constituency_with_code;
+    use crate::{ArchivedSummary, ConstituencyStatus};
+    use chrono::{DateTime, TimeZone, Utc};
+
+    fn time(hour: u32) -> DateTime<Utc> {
+        return Utc.with_ymd_and_hms(2024, 6, 1, hour, 0, 0).unwrap();
+    }
+
+    fn constituency(ons_code: &str, name: &str, labour: f64) -> ConstituencyStatus {
+        return ConstituencyStatus {
+            constituency: name.to_string(),
+            ..constituency_with_code(
+                ons_code,
+                &[
+                    (PartyName::Labour, labour),
+                    (PartyName::Conservatives, 1.0 - labour),
+                ],
+            )
+        };
+    }
+
+    fn status(hour: u32, constituencies: Vec<ConstituencyStatus>) -> Status {
+        return Status {
+            fetched_at: time(hour),
+            constituencies,
+            market_errors: Vec::new(),
+            unparsed_answers: Vec::new(),
+        };
+    }
+
+    #[test]
+    fn follows_a_seat_through_a_rename() {
+        let statuses = vec![
+            status(
+                1,
+                vec![
+                    constituency("E1", "Ynys Mon", 0.7),
+                    constituency("", "Unmatched", 0.9),
+                ],
+            ),
+            status(
+                2,
+                vec![
+                    constituency("E1", "Ynys Môn", 0.4),
+                    constituency("E2", "Chorley", 0.6),
+                ],
+            ),
+        ];
+        let series = make_time_series(&statuses, &[]).constituencies;
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0].ons_code, "E1");
+        assert_eq!(series[0].constituency, "Ynys Môn");
+        let favourites: Vec<(PartyName, f64)> = series[0]
+            .points
+            .iter()
+            .map(|point| (point.favourite.clone(), point.favourite_probability))
+            .collect();
+        assert_eq!(
+            favourites,
+            vec![(PartyName::Labour, 0.7), (PartyName::Conservatives, 0.6)]
+        );
+        assert_eq!(series[1].ons_code, "E2");
+        assert_eq!(series[1].points.len(), 1);
+    }
+
+    #[test]
+    fn party_points_use_the_aggregate_of_the_same_run() {
+        let statuses = vec![
+            status(1, vec![constituency("E1", "A", 0.7)]),
+            status(2, vec![constituency("E1", "A", 0.3)]),
+        ];
+        let aggregates = vec![ArchivedAggregate {
+            fetched_at: time(2),
+            monte_carlo_summary: vec![ArchivedSummary {
+                party: PartyName::Conservatives,
+                median: 400,
+                lower_5th: 350,
+                upper_95th: 450,
+                majority_percentage: 0.9,
+            }],
+        }];
+        let parties = make_time_series(&statuses, &aggregates).parties;
+        let parties: Vec<&PartyName> = parties.iter().map(|series| &series.party).collect();
+        assert_eq!(parties, vec![&PartyName::Conservatives, &PartyName::Labour]);
+
+        let series = make_time_series(&statuses, &aggregates).parties;
+        let conservatives = &series[0].points;
+        assert_eq!(conservatives[0].favourite_seats, 0);
+        assert_eq!(conservatives[0].median, None);
+        assert_eq!(conservatives[1].favourite_seats, 1);
+        assert_eq!(conservatives[1].median, Some(400));
+        assert_eq!(conservatives[1].majority_percentage, Some(0.9));
+        // missing from the aggregate's summary, so no seats
+        let labour = &series[1].points;
+        assert_eq!(labour[0].favourite_seats, 1);
+        assert_eq!(labour[1].median, Some(0));
+    }
+}
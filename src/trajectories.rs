@@ -0,0 +1,88 @@
+use chrono::{DateTime, Utc};
+
+use crate::manifold::Bet;
+use crate::{Mover, Trajectories, TrajectoryPoint};
+
+/// Rebuild an answer's probability over time from the bets placed on it.
+/// Only bets on the answer itself are recorded, so in markets where the
+/// answers sum to one the moves caused by bets on other answers show up at
+/// the next bet on this one (or at `fetched_at`).
+pub fn reconstruct_trajectory(
+    bets: &[Bet],
+    answer_id: &str,
+    current_probability: f64,
+    fetched_at: &DateTime<Utc>,
+) -> Vec<TrajectoryPoint> {
+    let mut answer_bets: Vec<&Bet> = bets
+        .iter()
+        .filter(|bet| bet.answer_id.as_deref() == Some(answer_id))
+        // unfilled limit orders don't move the price
+        .filter(|bet| bet.prob_before != bet.prob_after)
+        .collect();
+    answer_bets.sort_by_key(|bet| bet.created_time);
+
+    let mut points = Vec::new();
+    for bet in answer_bets {
+        let time = match DateTime::from_timestamp_millis(bet.created_time) {
+            Some(time) => time,
+            None => continue,
+        };
+        if points.is_empty() {
+            points.push(TrajectoryPoint {
+                time,
+                probability: bet.prob_before,
+            });
+        }
+        points.push(TrajectoryPoint {
+            time,
+            probability: bet.prob_after,
+        });
+    }
+    points.push(TrajectoryPoint {
+        time: *fetched_at,
+        probability: current_probability,
+    });
+    return points;
+}
+
+/// The probability at a given time, i.e. the latest point at or before it.
+/// Before the first point the first probability is used.
+pub fn probability_at(points: &[TrajectoryPoint], time: &DateTime<Utc>) -> Option<f64> {
+    return points
+        .iter()
+        .rev()
+        .find(|point| point.time <= *time)
+        .or(points.first())
+        .map(|point| point.probability);
+}
+
+/// The answers whose probability has changed the most since `since`,
+/// biggest change (either way) first.
+pub fn biggest_movers(
+    trajectories: &Trajectories,
+    since: &DateTime<Utc>,
+    count: usize,
+) -> Vec<Mover> {
+    let mut movers: Vec<Mover> = Vec::new();
+    for constituency in &trajectories.constituencies {
+        for answer in &constituency.answers {
+            let probability_before = probability_at(&answer.points, since);
+            let probability_now = probability_at(&answer.points, &trajectories.fetched_at);
+            if let (Some(probability_before), Some(probability_now)) =
+                (probability_before, probability_now)
+            {
+                movers.push(Mover {
+                    ons_code: constituency.ons_code.clone(),
+                    constituency: constituency.constituency.clone(),
+                    party: answer.party.clone(),
+                    probability_before,
+                    probability_now,
+                });
+            }
+        }
+    }
+    let change = |mover: &Mover| (mover.probability_now - mover.probability_before).abs();
+    movers.sort_by(|a, b| change(b).partial_cmp(&change(a)).unwrap());
+    movers.truncate(count);
+    return movers;
+}
@@ -0,0 +1,83 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use election_2024::archive::read_archived;
+use election_2024::{MarketErrorKind, PartyName, Status};
+
+const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/snapshot");
+
+/// Run download_data in a fresh directory, replaying the recorded snapshot,
+/// and return what it wrote to `out/constituencies.json`.
+fn replay(directory: &Path) -> Vec<u8> {
+    let _ = std::fs::remove_dir_all(directory);
+    std::fs::create_dir_all(directory.join("out")).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_download_data"))
+        .current_dir(directory)
+        .args(["--replay", SNAPSHOT, "--archive-dir", "archive"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    return std::fs::read(directory.join("out/constituencies.json")).unwrap();
+}
+
+#[test]
+fn replays_a_recorded_snapshot_into_a_status() {
+    let directory: PathBuf =
+        std::env::temp_dir().join(format!("election_2024_replay_{}", std::process::id()));
+    let output = replay(&directory);
+    let status: Status = serde_json::from_slice(&output).unwrap();
+
+    assert_eq!(status.fetched_at.to_rfc3339(), "2024-06-01T12:00:00+00:00");
+    let archived: Status =
+        read_archived(&directory.join("archive/status-20240601T120000Z.json.gz")).unwrap();
+    assert_eq!(archived.constituencies.len(), status.constituencies.len());
+
+    let codes: Vec<&str> = status
+        .constituencies
+        .iter()
+        .map(|constituency| constituency.ons_code.as_str())
+        .collect();
+    assert_eq!(codes, vec!["E14001065", "E14001266", "W07000112"]);
+
+    // both Lib Dem answers are merged into one party
+    let altrincham = &status.constituencies[0];
+    assert_eq!(altrincham.parties.len(), 3);
+    let lib_dems = altrincham
+        .parties
+        .iter()
+        .find(|party| party.name == PartyName::LiberalDemocrats)
+        .unwrap();
+    assert!((lib_dems.probability - 0.08).abs() < 1e-9);
+    assert_eq!(altrincham.market.unique_bettors, 23);
+
+    // the question without `in` still matches, and the unknown answer is kept
+    let harborough = &status.constituencies[1];
+    assert_eq!(harborough.constituency, "Harborough, Oadby and Wigston");
+    assert!(harborough
+        .parties
+        .iter()
+        .any(|party| party.name == PartyName::Unparsed("Yorkshire Party".to_string())));
+    assert_eq!(status.unparsed_answers.len(), 1);
+    assert_eq!(status.unparsed_answers[0].text, "Yorkshire Party");
+
+    // the unmatched market and the one that couldn't be fetched are reported,
+    // the test market is silently dropped
+    assert_eq!(status.market_errors.len(), 2);
+    assert!(matches!(
+        &status.market_errors[0].kind,
+        MarketErrorKind::UnmatchedConstituency { name } if name == "Atlantis"
+    ));
+    assert_eq!(status.market_errors[1].market_id, "broken");
+    assert!(matches!(
+        status.market_errors[1].kind,
+        MarketErrorKind::FetchFailed { .. }
+    ));
+
+    // replaying again gives exactly the same output
+    assert_eq!(replay(&directory), output);
+    std::fs::remove_dir_all(&directory).unwrap();
+}